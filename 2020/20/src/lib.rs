@@ -1,15 +1,56 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::iter;
 use std::mem;
 
 use itertools::Itertools;
 use lazy_static::lazy_static;
+use rand::prelude::*;
 use regex::Regex;
 
 pub type Pixel = bool;
 pub type Image = Vec<Vec<Pixel>>;
 pub type TileId = u64;
-type Border = u16;
+
+/// One edge of a tile, read clockwise around the tile, one bit per pixel.
+///
+/// The pixels are packed 64 to a word, first pixel in the highest bit, so an edge of up to 64
+/// pixels is a single word.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Border {
+    bits: Vec<u64>,
+    width: u32,
+}
+
+impl Border {
+    fn from_bools(it: impl IntoIterator<Item = bool>) -> Self {
+        let mut border = Self {
+            bits: vec![],
+            width: 0,
+        };
+        for b in it {
+            if border.width.is_multiple_of(u64::BITS) {
+                border.bits.push(0);
+            }
+            let word = border.bits.last_mut().expect("a word was just pushed");
+            *word = *word << 1 | u64::from(b);
+            border.width += 1;
+        }
+        border
+    }
+
+    fn pixels(&self) -> impl DoubleEndedIterator<Item = bool> + '_ {
+        (0..self.width).map(|i| {
+            let word = (i / u64::BITS) as usize;
+            let word_width = (self.width - i / u64::BITS * u64::BITS).min(u64::BITS);
+            self.bits[word] >> (word_width - 1 - i % u64::BITS) & 1 == 1
+        })
+    }
+
+    /// The same edge read in the other direction, which is how the neighbor sharing it reads it.
+    fn complement(&self) -> Self {
+        Self::from_bools(self.pixels().rev())
+    }
+}
 
 fn char_to_pixel(c: char) -> Pixel {
     match c {
@@ -27,16 +68,6 @@ pub fn strs_to_image<'a>(s: impl IntoIterator<Item = &'a str>) -> Image {
     s.into_iter().map(str_to_image_row).collect()
 }
 
-fn bools_to_border(it: impl IntoIterator<Item = bool>) -> Border {
-    it.into_iter().fold(0, |mut accu, b| {
-        accu *= 2;
-        if b {
-            accu += 1;
-        }
-        accu
-    })
-}
-
 pub fn flip_image(image: &mut Image) {
     image.reverse()
 }
@@ -69,13 +100,16 @@ pub fn remove_subimage(image: &mut Image, target: &Image) {
             return;
         }
         let tw = target[0].len();
+        let (Some(max_i), Some(max_j)) = (ih.checked_sub(th), iw.checked_sub(tw)) else {
+            return;
+        };
         let true_loc = || {
             (0..th)
                 .cartesian_product(0..tw)
                 .filter(|&(p, q)| target[p][q])
         };
-        for (i, j) in (0..ih - th).cartesian_product(0..iw - tw) {
-            if true_loc().map(|(p, q)| image[i + p][j + q]).all(|x| x) {
+        for (i, j) in (0..=max_i).cartesian_product(0..=max_j) {
+            if true_loc().all(|(p, q)| image[i + p][j + q]) {
                 true_loc().for_each(|(p, q)| image[i + p][j + q] = false);
             }
         }
@@ -103,8 +137,8 @@ pub fn image_string(image: &Image) -> String {
 #[derive(Clone, Debug)]
 pub struct Tile {
     pub id: TileId,
+    /// Top, right, bottom and left, each read clockwise.
     borders: [Border; 4],
-    neighbors: [Option<TileId>; 4],
     pub image: Image,
 }
 impl Tile {
@@ -116,63 +150,62 @@ impl Tile {
         let cap = &TITLE_RE.captures(title_line).unwrap()[1];
         let id = cap.parse::<TileId>().unwrap();
         let image: Image = strs_to_image(s[1..].iter().copied());
-        let border1 = bools_to_border(image[0].iter().copied());
-        let border2 = bools_to_border(image.iter().map(|x| *x.last().unwrap()));
-        let border3 = bools_to_border(image.last().unwrap().iter().copied().rev());
-        let border4 = bools_to_border(image.iter().map(|x| x[0]).rev());
+        let size = image.len();
+        assert!(
+            size >= 2 && image.iter().all(|row| row.len() == size),
+            "tile {} should be a square of at least 2x2 pixels",
+            id
+        );
+        let border1 = Border::from_bools(image[0].iter().copied());
+        let border2 = Border::from_bools(image.iter().map(|x| *x.last().unwrap()));
+        let border3 = Border::from_bools(image.last().unwrap().iter().copied().rev());
+        let border4 = Border::from_bools(image.iter().map(|x| x[0]).rev());
         let border = [border1, border2, border3, border4];
         Self {
             id,
             image,
             borders: border,
-            neighbors: Default::default(),
         }
     }
 
-    fn borders_candidates(&self) -> impl Iterator<Item = Border> {
-        self.borders
-            .clone()
-            .into_iter()
-            .flat_map(|b| [b, complement(b)])
+    /// Width and height of the tile, in pixels.
+    pub fn size(&self) -> usize {
+        self.image.len()
     }
 
     fn flip(&mut self) {
         flip_image(&mut self.image);
         self.borders.swap(0, 2);
-        self.neighbors.swap(0, 2);
-        self.borders.iter_mut().for_each(|i| *i = complement(*i));
+        self.borders.iter_mut().for_each(|i| *i = i.complement());
     }
 
     fn rotate(&mut self) {
         // rotate clockwise
         rotate_image(&mut self.image);
         self.borders.rotate_right(1);
-        self.neighbors.rotate_right(1);
     }
 
-    fn neighbors(&self) -> impl Iterator<Item = TileId> {
-        self.neighbors.clone().into_iter().filter_map(|x| x)
-    }
-
-    fn match_adj_border(&mut self, direction: usize, target_border: Border) {
-        let my_target_border = complement(target_border);
-        if !self.borders.contains(&my_target_border) {
-            self.flip();
-        }
-        assert!(self.borders.contains(&my_target_border));
-        while self.borders[direction] != my_target_border {
-            self.rotate();
-        }
+    /// The tile in each of its 8 orientations: 4 rotations, then 4 rotations of the flipped tile.
+    fn orientations(&self) -> impl Iterator<Item = Tile> {
+        let mut tile = self.clone();
+        (0..8).map(move |i| {
+            if i == 4 {
+                tile.flip();
+            }
+            let out = tile.clone();
+            tile.rotate();
+            out
+        })
     }
 }
 
-pub fn input_to_tiles<'a>(s: &'a str) -> impl Iterator<Item = Tile> + 'a {
+pub fn input_to_tiles(s: &str) -> impl Iterator<Item = Tile> + '_ {
     fn sep_empty_line<'a>(
         mut it: impl Iterator<Item = &'a str>,
     ) -> impl Iterator<Item = Vec<&'a str>> {
-        return std::iter::from_fn(move || {
+        std::iter::from_fn(move || {
             let mut v = vec![];
-            while let Some(s) = it.next() {
+            for s in it.by_ref() {
                 if s.trim().is_empty() {
                     return Some(v);
                 }
@@ -183,74 +216,30 @@ pub fn input_to_tiles<'a>(s: &'a str) -> impl Iterator<Item = Tile> + 'a {
             } else {
                 Some(v)
             }
-        });
+        })
     }
     sep_empty_line(s.lines()).map(|v| Tile::new(&v))
 }
 
-fn complement(mut i: Border) -> Border {
-    let mut out = 0;
-    for _ in 0..10 {
-        out <<= 1;
-        out += i % 2;
-        i /= 2;
-    }
-    out
-}
-
 pub struct SeaMap {
     tiles: Vec<Vec<Tile>>,
+    tile_size: usize,
     pub image: Vec<Vec<bool>>,
 }
 
 impl SeaMap {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         Self::new(input_to_tiles(s))
     }
     pub fn new(tile: impl Iterator<Item = Tile>) -> Self {
-        let mut tile_by_id: HashMap<_, _> = tile.map(|t| (t.id, t)).collect();
-        connect_tiles(&mut tile_by_id);
-        fn build_row(first_tile: Tile, tile_by_id: &mut HashMap<TileId, Tile>) -> Vec<Tile> {
-            let mut row = vec![];
-            let mut last_tile = first_tile;
-            while let (Some(next_id), last_border) = (last_tile.neighbors[1], last_tile.borders[1])
-            {
-                let mut next_tile = tile_by_id.remove(&next_id).unwrap();
-                let this_border = complement(last_border);
-                if !next_tile.borders.contains(&this_border) {
-                    next_tile.flip();
-                }
-                while next_tile.borders[3] != this_border {
-                    next_tile.rotate();
-                }
-                row.push(last_tile);
-                last_tile = next_tile;
-            }
-            row.push(last_tile);
-            row
-        }
-        let lu_id = tile_by_id
-            .iter()
-            .find_map(|(id, t)| (t.neighbors().count() == 2).then(|| *id))
-            .unwrap();
-        let mut row_begin = tile_by_id.remove(&lu_id).unwrap();
-        while row_begin.neighbors[0].is_some() || row_begin.neighbors[3].is_some() {
-            row_begin.rotate();
-        }
-        let mut tiles = vec![];
-        loop {
-            let row = build_row(row_begin, &mut tile_by_id);
-            let next_row_begin_id = row[0].neighbors[2];
-            let prev_row_begin_down_border = row[0].borders[2];
-            tiles.push(row);
-            let mut next_row_begin = match next_row_begin_id {
-                None => break,
-                Some(id) => tile_by_id.remove(&id).unwrap(),
-            };
-            next_row_begin.match_adj_border(0, prev_row_begin_down_border);
-            row_begin = next_row_begin;
-        }
-        assert!(tile_by_id.is_empty());
+        let tiles = tile.collect_vec();
+        let tile_size = tiles.first().map_or(0, Tile::size);
+        assert!(
+            tiles.iter().all(|t| t.size() == tile_size),
+            "all tiles should have the same size"
+        );
+        let tiles = arrange_tiles(tiles);
         let mut rows: Vec<Vec<Pixel>> = vec![];
         fn trimmed_pixel_rows_from_tile(t: &Tile) -> impl Iterator<Item = &[Pixel]> {
             let image = &t.image;
@@ -273,7 +262,16 @@ impl SeaMap {
             rows.extend(new_rows_iter)
         }
 
-        Self { tiles, image: rows }
+        Self {
+            tiles,
+            tile_size,
+            image: rows,
+        }
+    }
+
+    /// Width and height of every tile, in pixels, as found in the input.
+    pub fn tile_size(&self) -> usize {
+        self.tile_size
     }
     pub fn tiles(&self) -> impl Iterator<Item = &Tile> {
         self.tiles.iter().flat_map(|i| i.iter())
@@ -302,68 +300,175 @@ impl SeaMap {
     }
 }
 
-fn connect_tiles(tile_by_id: &mut HashMap<TileId, Tile>) {
-    let mut border_to_id = {
-        let mut s: HashMap<_, _> = HashMap::new();
-        for (id, border) in tile_by_id
-            .iter()
-            .flat_map(|(id, t)| t.borders_candidates().map(|x| (*id, x)))
-        {
-            s.entry(border).or_insert(HashSet::new()).insert(id);
-        }
-        s
+/// Lay the tiles out on a square grid so that every pair of adjacent borders match.
+///
+/// Panics if there is no such arrangement.
+fn arrange_tiles(tiles: Vec<Tile>) -> Vec<Vec<Tile>> {
+    let side = (1..).find(|n| n * n >= tiles.len()).unwrap();
+    assert_eq!(side * side, tiles.len(), "the tiles should form a square");
+    let oriented = tiles
+        .iter()
+        .map(|t| t.orientations().collect_vec())
+        .collect_vec();
+    // Number the distinct borders, so the search compares and hashes plain integers.
+    let mut border_ids: HashMap<Border, usize> = HashMap::new();
+    let mut border_id = |b: Border| {
+        let next = border_ids.len();
+        *border_ids.entry(b).or_insert(next)
     };
-    loop {
-        fn add_neighbor(
-            tile_map: &mut HashMap<TileId, Tile>,
-            lid: TileId,
-            lborder: Border,
-            rid: TileId,
-        ) -> usize {
-            let l = tile_map.get_mut(&lid).unwrap();
-            let border_idx = l
-                .borders
+    // For every oriented tile: its top and left border, and the top and left border that its
+    // bottom and right neighbors need.
+    let shapes = oriented
+        .iter()
+        .map(|orientations| {
+            orientations
                 .iter()
-                .position(|&p| p == lborder || complement(p) == lborder)
-                .unwrap();
-            l.neighbors[border_idx] = Some(rid);
-            return l.neighbors().count();
+                .map(|t| Shape {
+                    top: border_id(t.borders[0].clone()),
+                    left: border_id(t.borders[3].clone()),
+                    below: border_id(t.borders[2].complement()),
+                    right: border_id(t.borders[1].complement()),
+                })
+                .collect_vec()
+        })
+        .collect_vec();
+    // Candidates by their top border, left border, or both.
+    let mut by_top: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+    let mut by_left: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+    let mut by_left_top: HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+    for (i, orientations) in shapes.iter().enumerate() {
+        for (o, shape) in orientations.iter().enumerate() {
+            by_top.entry(shape.top).or_default().push((i, o));
+            by_left.entry(shape.left).or_default().push((i, o));
+            by_left_top
+                .entry((shape.left, shape.top))
+                .or_default()
+                .push((i, o));
         }
-        let mut done = true;
-        let mut border_to_remove = HashSet::new();
-        let mut tile_id_to_remove = HashSet::new();
-        for (&border, ids) in border_to_id.iter().filter(|(_border, s)| s.len() == 2) {
-            done = false;
-            if border_to_remove.contains(&complement(border)) {
-                continue;
-            }
-            let mut id_iter = ids.iter();
-            let lid = *id_iter.next().unwrap();
-            let rid = *id_iter.next().unwrap();
-            let l_neighbor_count = add_neighbor(tile_by_id, lid, border, rid);
-            let r_neighbor_count = add_neighbor(tile_by_id, rid, border, lid);
-            border_to_remove.extend([border, complement(border)]);
-            if l_neighbor_count == 4 {
-                tile_id_to_remove.insert(lid);
-            }
-            if r_neighbor_count == 4 {
-                tile_id_to_remove.insert(rid);
+    }
+    // A border that no other tile has, in any orientation, has to face the outside of the
+    // picture. The converse only holds when there are exactly as many of those as the outside
+    // has room for, and then the top left tile shows them on its top and left.
+    let is_unmatched = |i: usize, border: usize| {
+        by_top
+            .get(&border)
+            .is_none_or(|v| v.iter().all(|&(j, _)| j == i))
+    };
+    let unmatched = shapes
+        .iter()
+        .enumerate()
+        .map(|(i, orientations)| {
+            let s = &orientations[0];
+            [s.top, s.right, s.below, s.left]
+                .into_iter()
+                .filter(|&b| is_unmatched(i, b))
+                .count()
+        })
+        .sum::<usize>();
+    let all = (0..tiles.len()).cartesian_product(0..8).collect_vec();
+    let corners = all
+        .iter()
+        .copied()
+        .filter(|&(i, o)| {
+            let s = &shapes[i][o];
+            is_unmatched(i, s.top) && is_unmatched(i, s.left)
+        })
+        .collect_vec();
+    let seeds = if unmatched == 4 * side { corners } else { all };
+
+    struct Shape {
+        top: usize,
+        left: usize,
+        below: usize,
+        right: usize,
+    }
+    struct Search<'a> {
+        side: usize,
+        shapes: &'a [Vec<Shape>],
+        by_top: &'a HashMap<usize, Vec<(usize, usize)>>,
+        by_left: &'a HashMap<usize, Vec<(usize, usize)>>,
+        by_left_top: &'a HashMap<(usize, usize), Vec<(usize, usize)>>,
+        seeds: &'a [(usize, usize)],
+        used: Vec<bool>,
+        placed: Vec<(usize, usize)>,
+        rng: StdRng,
+        budget: usize,
+        gave_up: bool,
+    }
+    impl Search<'_> {
+        fn shape(&self, (i, o): (usize, usize)) -> &Shape {
+            &self.shapes[i][o]
+        }
+        fn run(&mut self) -> bool {
+            let pos = self.placed.len();
+            if pos == self.used.len() {
+                return true;
             }
+            let (row, col) = (pos / self.side, pos % self.side);
+            let up = (row > 0).then(|| self.shape(self.placed[pos - self.side]).below);
+            let left = (col > 0).then(|| self.shape(self.placed[pos - 1]).right);
+            let candidates = match (left, up) {
+                (Some(l), Some(u)) => self.by_left_top.get(&(l, u)),
+                (Some(l), None) => self.by_left.get(&l),
+                (None, Some(u)) => self.by_top.get(&u),
+                (None, None) => return self.try_each(self.seeds),
+            };
+            candidates.is_some_and(|c| self.try_each(c))
         }
-        border_to_remove.into_iter().for_each(|b| {
-            border_to_id.remove(&b).unwrap();
-        });
-        tile_id_to_remove.into_iter().for_each(|tid| {
-            for border in tile_by_id[&tid].borders_candidates() {
-                if let Some(s) = border_to_id.get_mut(&border) {
-                    s.remove(&tid);
+        fn try_each(&mut self, candidates: &[(usize, usize)]) -> bool {
+            let (tail, head) = candidates.split_at(self.rng.gen_range(0..candidates.len()));
+            for &(i, o) in head.iter().chain(tail) {
+                if self.used[i] {
+                    continue;
+                }
+                if self.budget == 0 {
+                    self.gave_up = true;
+                    return false;
                 }
+                self.budget -= 1;
+                self.used[i] = true;
+                self.placed.push((i, o));
+                if self.run() {
+                    return true;
+                }
+                self.placed.pop();
+                self.used[i] = false;
             }
-        });
-        if done {
+            false
+        }
+    }
+
+    let mut search = Search {
+        side,
+        shapes: &shapes,
+        by_top: &by_top,
+        by_left: &by_left,
+        by_left_top: &by_left_top,
+        seeds: &seeds,
+        used: vec![false; tiles.len()],
+        placed: vec![],
+        rng: StdRng::seed_from_u64(0),
+        budget: 0,
+        gave_up: false,
+    };
+    // Small tiles make for plenty of solutions, but a mistake may only show in the last row and
+    // take exponential time to undo. Short runs that start from other candidates find one sooner.
+    for restart in 0.. {
+        search.used.fill(false);
+        search.placed.clear();
+        search.rng = StdRng::seed_from_u64(restart);
+        search.budget = tiles.len() * (restart as usize + 1);
+        search.gave_up = false;
+        if search.run() {
             break;
         }
+        assert!(search.gave_up, "the tiles cannot be arranged");
     }
+    search
+        .placed
+        .chunks(side)
+        .map(|row| row.iter().map(|&(i, o)| oriented[i][o].clone()).collect())
+        .collect()
 }
 
 pub fn solve_1(s: &str) -> u64 {
@@ -374,6 +479,7 @@ pub fn solve_1(s: &str) -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+
     #[test]
     fn it_works() {
         assert_eq!(solve_1(SAMPLE_IN), 20899048083289)
@@ -386,11 +492,7 @@ mod tests {
  #  #  #  #  #  #   ";
         let monster: Image = MONSTER
             .lines()
-            .map(|s| {
-                s.chars()
-                    .map(|c| if c == '#' { true } else { false })
-                    .collect()
-            })
+            .map(|s| s.chars().map(|c| c == '#').collect())
             .collect();
         let mut sea_map = SeaMap::from_str(SAMPLE_IN);
         remove_subimage(&mut sea_map.image, &monster);
@@ -409,13 +511,31 @@ mod tests {
     #[test]
     fn test_complement() {
         for i in 0..1024 {
+            let border = Border {
+                bits: vec![i],
+                width: 10,
+            };
             assert_eq!(
-                i,
-                complement(complement(i)),
+                border,
+                border.complement().complement(),
                 "test failed while computing complement {}",
                 i
             )
         }
+        for width in 1..=64 {
+            let border = Border::from_bools((0..width).map(|i| i == 0));
+            assert_eq!(border.width, width);
+            assert_eq!(border.complement().bits, [1]);
+        }
+        // Wider borders take several words, the last one holding the remaining pixels.
+        for width in [65, 100, 128, 130] {
+            let border = Border::from_bools((0..width).map(|i| i == 0));
+            assert_eq!(border.bits.len(), (width as usize).div_ceil(64));
+            assert_eq!(border.bits[0], 1 << 63);
+            let complement = border.complement();
+            assert_eq!(complement.bits.last(), Some(&1));
+            assert_eq!(complement.complement(), border);
+        }
     }
     const SAMPLE_TILE: &str = r"Tile 2311:
 ..##.#..#.
@@ -436,29 +556,29 @@ mod tests {
     #[test]
     fn test_border_seq() {
         let tile = make_sample_tile();
-        assert_eq!(tile.borders[0], 0b0011010010);
-        assert_eq!(tile.borders[1], 0b0001011001);
-        assert_eq!(tile.borders[2], 0b1110011100);
-        assert_eq!(tile.borders[3], 0b0100111110);
+        assert_eq!(tile.borders[0].bits, [0b0011010010]);
+        assert_eq!(tile.borders[1].bits, [0b0001011001]);
+        assert_eq!(tile.borders[2].bits, [0b1110011100]);
+        assert_eq!(tile.borders[3].bits, [0b0100111110]);
     }
 
     #[test]
     fn test_border_flip() {
         let mut tile = make_sample_tile();
         tile.flip();
-        assert_eq!(tile.borders[0], 0b0011100111);
-        assert_eq!(tile.borders[1], 0b1001101000);
-        assert_eq!(tile.borders[2], 0b0100101100);
-        assert_eq!(tile.borders[3], 0b0111110010);
+        assert_eq!(tile.borders[0].bits, [0b0011100111]);
+        assert_eq!(tile.borders[1].bits, [0b1001101000]);
+        assert_eq!(tile.borders[2].bits, [0b0100101100]);
+        assert_eq!(tile.borders[3].bits, [0b0111110010]);
     }
     #[test]
     fn test_border_rotate() {
         let mut tile = make_sample_tile();
         tile.rotate();
-        assert_eq!(tile.borders[0], 0b0100111110);
-        assert_eq!(tile.borders[1], 0b0011010010);
-        assert_eq!(tile.borders[2], 0b0001011001);
-        assert_eq!(tile.borders[3], 0b1110011100);
+        assert_eq!(tile.borders[0].bits, [0b0100111110]);
+        assert_eq!(tile.borders[1].bits, [0b0011010010]);
+        assert_eq!(tile.borders[2].bits, [0b0001011001]);
+        assert_eq!(tile.borders[3].bits, [0b1110011100]);
     }
     fn make_small_tile() -> Tile {
         const TILE: &str = r"Tile 0:
@@ -494,6 +614,144 @@ mod tests {
         let target_tile = make_small_tile_rotated();
         assert_eq!(&tile.borders, &target_tile.borders)
     }
+    /// A puzzle with a `side` x `side` grid of tiles, each `size` pixels wide, shuffled, turned
+    /// and flipped at random. Returns the input text, the expected image and the ids of the four
+    /// corner tiles.
+    fn make_puzzle(side: usize, size: usize, seed: u64) -> (String, Image, Vec<TileId>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut grid: Vec<Vec<Image>> = (0..side)
+            .map(|_| {
+                (0..side)
+                    .map(|_| {
+                        (0..size)
+                            .map(|_| (0..size).map(|_| rng.gen()).collect())
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        // Neighbors share the pixels of the edge between them.
+        for (r, c) in (0..side).cartesian_product(0..side) {
+            if c > 0 {
+                let edge = grid[r][c - 1].iter().map(|row| row[size - 1]).collect_vec();
+                for (row, pixel) in grid[r][c].iter_mut().zip(edge) {
+                    row[0] = pixel;
+                }
+            }
+            if r > 0 {
+                grid[r][c][0] = grid[r - 1][c][size - 1].clone();
+            }
+        }
+        let image = grid
+            .iter()
+            .flat_map(|row| {
+                (1..size - 1).map(move |y| {
+                    row.iter()
+                        .flat_map(|tile| tile[y][1..size - 1].iter().copied())
+                        .collect()
+                })
+            })
+            .collect();
+        let mut ids = (1000..10000).choose_multiple(&mut rng, side * side);
+        ids.shuffle(&mut rng);
+        let corners = [0, side - 1, side * (side - 1), side * side - 1].map(|i| ids[i]);
+        let mut tiles = grid.into_iter().flatten().zip(ids).collect_vec();
+        tiles.shuffle(&mut rng);
+        let text = tiles
+            .into_iter()
+            .map(|(mut tile, id)| {
+                if rng.gen() {
+                    flip_image(&mut tile);
+                }
+                for _ in 0..rng.gen_range(0..4) {
+                    rotate_image(&mut tile);
+                }
+                format!("Tile {}:\n{}", id, image_string(&tile))
+            })
+            .join("\n");
+        (text, image, corners.to_vec())
+    }
+
+    /// Whether `image` is `target` turned and flipped in some way.
+    fn same_up_to_orientation(image: &Image, target: &Image) -> bool {
+        let mut image = image.clone();
+        (0..8).any(|i| {
+            if i == 4 {
+                flip_image(&mut image);
+            }
+            rotate_image(&mut image);
+            &image == target
+        })
+    }
+
+    fn assert_borders_match(sea_map: &SeaMap) {
+        let tiles = &sea_map.tiles;
+        for (r, c) in (0..tiles.len()).cartesian_product(0..tiles.len()) {
+            if c > 0 {
+                assert_eq!(
+                    tiles[r][c].borders[3],
+                    tiles[r][c - 1].borders[1].complement()
+                );
+            }
+            if r > 0 {
+                assert_eq!(
+                    tiles[r][c].borders[0],
+                    tiles[r - 1][c].borders[2].complement()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_tile_sizes() {
+        for (side, size) in [(3, 10), (12, 10), (4, 32), (2, 64), (3, 100)] {
+            let (text, image, corners) = make_puzzle(side, size, size as u64);
+            let sea_map = SeaMap::from_str(&text);
+            assert_eq!(sea_map.tile_size(), size);
+            assert_borders_match(&sea_map);
+            assert!(same_up_to_orientation(&sea_map.image, &image));
+            assert_eq!(
+                sea_map.corners().map(|t| t.id).sorted().collect_vec(),
+                corners.into_iter().sorted().collect_vec()
+            );
+        }
+    }
+
+    #[test]
+    fn test_small_tiles() {
+        // Three bits only make 6 distinct borders, so these puzzles can have several solutions;
+        // any arrangement where all borders match will do.
+        for (side, seed) in [(2, 1), (3, 2), (5, 3), (10, 4)] {
+            let (text, image, _) = make_puzzle(side, 3, seed);
+            let sea_map = SeaMap::from_str(&text);
+            assert_eq!(sea_map.tile_size(), 3);
+            assert_borders_match(&sea_map);
+            assert_eq!(sea_map.tiles().count(), side * side);
+            assert_eq!(sea_map.image.len(), image.len());
+        }
+    }
+
+    #[test]
+    fn test_many_small_tiles() {
+        // Backtracking alone took minutes on some of these.
+        for seed in 0..30 {
+            let (text, _, _) = make_puzzle(10, 3, seed);
+            assert_borders_match(&SeaMap::from_str(&text));
+        }
+    }
+
+    #[test]
+    fn test_remove_subimage_at_edges() {
+        let target = strs_to_image(["##", "#."]);
+        let mut image = strs_to_image(["....", "....", "..##", "..#."]);
+        remove_subimage(&mut image, &target);
+        assert_eq!(image_string(&image).matches('#').count(), 0);
+        // A target larger than the image is never found.
+        let mut small = strs_to_image(["#"]);
+        remove_subimage(&mut small, &target);
+        assert_eq!(small, strs_to_image(["#"]));
+    }
+
     #[test]
     fn test_combine_image() {
         let x = SeaMap::from_str(SAMPLE_IN);
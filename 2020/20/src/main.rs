@@ -23,7 +23,6 @@ fn main() {
     remove_subimage(&mut image, &sea_monster);
     let out2 = image
         .iter()
-        .map(|v| v.iter())
         .flatten()
         .copied()
         .filter(|&x| x)
@@ -1,62 +1,177 @@
 use itertools::Itertools;
 use std::io::{self, BufRead};
 
-#[derive(Debug, Copy, Clone)]
-enum Play {
-    Rock,
-    Paper,
-    Scissor,
-}
+/// A move, identified by its position in [`Game::moves`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Play(usize);
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum GameOutcome {
     Win,
     Lose,
     Draw,
 }
 
-impl Play {
-    fn score(&self) -> i32 {
-        match *self {
-            Play::Rock => 1,
-            Play::Paper => 2,
-            Play::Scissor => 3,
+#[derive(Debug, Clone)]
+struct Move {
+    name: String,
+    score: i32,
+}
+
+/// A game described by data: the moves, their scores, and which move beats which.
+#[derive(Debug, Clone)]
+struct Game {
+    moves: Vec<Move>,
+    /// `beats[i][j]`: move `i` beats move `j`. Pairs where neither beats the other are a draw.
+    beats: Vec<Vec<bool>>,
+}
+
+impl Game {
+    /// Moves are written as one letter each in the strategy guide.
+    const MAX_MOVES: usize = 26;
+
+    fn new(moves: Vec<Move>, beats: impl Fn(usize, usize) -> bool) -> anyhow::Result<Self> {
+        let n = moves.len();
+        if !(1..=Self::MAX_MOVES).contains(&n) {
+            anyhow::bail!(
+                "a game needs between 1 and {} moves, got {n}",
+                Self::MAX_MOVES
+            );
+        }
+        let beats = (0..n)
+            .map(|i| (0..n).map(|j| beats(i, j)).collect_vec())
+            .collect_vec();
+        for i in 0..n {
+            if beats[i][i] {
+                anyhow::bail!("{} beats itself", moves[i].name);
+            }
+            for j in 0..i {
+                if beats[i][j] && beats[j][i] {
+                    anyhow::bail!("{} and {} beat each other", moves[i].name, moves[j].name);
+                }
+            }
         }
+        Ok(Self { moves, beats })
     }
-    fn get_outcome(&self, other: &Self) -> GameOutcome {
-        use GameOutcome::*;
-        match *self {
-            Play::Rock => match *other {
-                Play::Rock => Draw,
-                Play::Paper => Lose,
-                Play::Scissor => Win,
-            },
-            Play::Scissor => match *other {
-                Play::Rock => Lose,
-                Play::Paper => Win,
-                Play::Scissor => Draw,
-            },
-            Play::Paper => match *other {
-                Play::Rock => Win,
-                Play::Paper => Draw,
-                Play::Scissor => Lose,
-            },
-        }
-    }
-    fn find_winner(&self) -> Self {
-        match *self {
-            Self::Rock => Self::Paper,
-            Self::Paper => Self::Scissor,
-            Self::Scissor => Self::Rock,
+
+    /// A balanced game for an odd number of moves: each move beats the moves an odd number of
+    /// steps before it (cyclically), and loses to the rest.
+    fn cyclic(moves: Vec<Move>) -> anyhow::Result<Self> {
+        let n = moves.len();
+        if n % 2 != 1 {
+            anyhow::bail!("a cyclic game needs an odd number of moves, got {n}");
         }
+        Self::new(moves, |i, j| (i + n - j) % n % 2 == 1)
     }
-    fn find_loser(&self) -> Self {
-        match *self {
-            Self::Rock => Self::Scissor,
-            Self::Scissor => Self::Paper,
-            Self::Paper => Self::Rock,
+
+    /// Moves scored 1, 2, 3... in order.
+    fn cyclic_from_names(names: &[&str]) -> anyhow::Result<Self> {
+        Self::cyclic(
+            names
+                .iter()
+                .zip(1..)
+                .map(|(name, score)| Move {
+                    name: name.to_string(),
+                    score,
+                })
+                .collect(),
+        )
+    }
+
+    fn rock_paper_scissors() -> Self {
+        Self::cyclic_from_names(&["Rock", "Paper", "Scissor"]).expect("the preset is valid")
+    }
+
+    fn rock_paper_scissors_lizard_spock() -> Self {
+        Self::cyclic_from_names(&["Rock", "Paper", "Scissor", "Spock", "Lizard"])
+            .expect("the preset is valid")
+    }
+
+    /// Read a game description with one move per line, in the order of the guide's letters:
+    ///
+    /// ```text
+    /// Rock 1 beats Scissor
+    /// Paper 2 beats Rock
+    /// Scissor 3 beats Paper
+    /// ```
+    ///
+    /// A move may beat several others, or none.
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        let lines = s
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| -> anyhow::Result<_> {
+                let mut words = line.split_whitespace();
+                let (name, score) = words
+                    .next_tuple()
+                    .ok_or_else(|| anyhow::anyhow!("expected a name and a score: {line:?}"))?;
+                let beaten = match words.next() {
+                    None => vec![],
+                    Some("beats") => words.collect_vec(),
+                    Some(_) => anyhow::bail!("expected `beats` after the score: {line:?}"),
+                };
+                let mv = Move {
+                    name: name.to_string(),
+                    score: score.parse()?,
+                };
+                Ok((mv, beaten))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let index = |name: &str| {
+            lines
+                .iter()
+                .position(|(mv, _)| mv.name == name)
+                .ok_or_else(|| anyhow::anyhow!("unknown move {name}"))
+        };
+        let beats = lines
+            .iter()
+            .map(|(_, beaten)| beaten.iter().map(|name| index(name)).try_collect())
+            .collect::<anyhow::Result<Vec<Vec<usize>>>>()?;
+        let moves = lines.into_iter().map(|(mv, _)| mv).collect();
+        Self::new(moves, |i, j| beats[i].contains(&j))
+    }
+
+    fn plays(&self) -> impl Iterator<Item = Play> {
+        (0..self.moves.len()).map(Play)
+    }
+
+    fn name(&self, play: Play) -> &str {
+        &self.moves[play.0].name
+    }
+
+    fn score(&self, play: Play) -> i32 {
+        self.moves[play.0].score
+    }
+
+    fn get_outcome(&self, play: Play, other: Play) -> GameOutcome {
+        if self.beats[play.0][other.0] {
+            GameOutcome::Win
+        } else if self.beats[other.0][play.0] {
+            GameOutcome::Lose
+        } else {
+            GameOutcome::Draw
         }
     }
+
+    /// A move that gets `outcome` against `opponent`. If several do, the best-scoring one.
+    fn choose(&self, opponent: Play, outcome: GameOutcome) -> Option<Play> {
+        self.plays()
+            .filter(|&p| self.get_outcome(p, opponent) == outcome)
+            .max_by_key(|&p| self.score(p))
+    }
+
+    /// The opponent's column uses `A`, `B`, `C`... in move order.
+    fn parse_opponent(&self, s: &str) -> Option<Play> {
+        let c = s.chars().exactly_one().ok()?;
+        let i = (c as usize).checked_sub('A' as usize)?;
+        (i < self.moves.len()).then_some(Play(i))
+    }
+
+    /// The letters of the player's column, ending at `Z`: `X`, `Y`, `Z` for three moves.
+    fn player_letters(&self) -> impl Iterator<Item = char> {
+        let n = self.moves.len() as u8;
+        (b'Z' + 1 - n..=b'Z').map(char::from)
+    }
 }
 
 impl GameOutcome {
@@ -70,68 +185,561 @@ impl GameOutcome {
 }
 
 fn main() -> anyhow::Result<()> {
+    const USAGE: &str =
+        "usage: aoc_22_02 [rps|rpsls|--game FILE] [--mappings] [--tournament] < input";
+    let mut game = Game::rock_paper_scissors();
+    let mut show_mappings = false;
+    let mut run_tournament = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "rps" => game = Game::rock_paper_scissors(),
+            "rpsls" => game = Game::rock_paper_scissors_lizard_spock(),
+            "--game" => {
+                let path = args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                game = Game::parse(&std::fs::read_to_string(path)?)?;
+            }
+            "--mappings" => show_mappings = true,
+            "--tournament" => run_tournament = true,
+            _ => anyhow::bail!(USAGE),
+        }
+    }
     let stdin = io::stdin();
     let stdin = stdin.lock();
     let games: Vec<(String, String)> = stdin
         .lines()
         .map(|line| -> anyhow::Result<(String, String)> {
             let line = line?;
-            let foo @ (_, _) = line
+            let pair @ (_, _) = line
                 .split_whitespace()
                 .map(str::to_string)
                 .collect_tuple()
                 .expect("Expect two words");
-            Ok(foo)
+            Ok(pair)
         })
         .try_collect()?;
-    println!("q1 result: {}", q1(&games));
-    println!("q2 result: {}", q2(&games));
+    println!("q1 result: {}", q1(&game, &games));
+    println!("q2 result: {}", q2(&game, &games)?);
+    if show_mappings {
+        let report = analyze_mappings(&game, &games);
+        for (mapping, score) in &report.scores {
+            println!("{}: {score}", format_mapping(&game, mapping));
+        }
+        println!(
+            "best: {} ({})",
+            format_mapping(&game, &report.best.0),
+            report.best.1
+        );
+        println!(
+            "worst: {} ({})",
+            format_mapping(&game, &report.worst.0),
+            report.worst.1
+        );
+    }
+    if run_tournament {
+        use tournament::*;
+        let guide = games
+            .iter()
+            .map(|(_, player)| parse_with_mapping(&game, player, &game.plays().collect()))
+            .collect();
+        let entrants = [
+            Entrant::new("guide", FollowGuide(guide)),
+            Entrant::new("random", Random),
+            Entrant::new("counter-most-frequent", CounterMostFrequent),
+            Entrant::new("markov", Markov::default()),
+        ];
+        for m in tournament::run(&game, &entrants, 1000, 20, 2022) {
+            println!(
+                "{} vs {}: {} wins to {}; {} / {}",
+                m.players.0, m.players.1, m.wins.0, m.wins.1, m.scores.0, m.scores.1
+            );
+        }
+    }
     Ok(())
 }
 
-fn q1(games: &[(String, String)]) -> i32 {
+fn parse_opponent(game: &Game, s: &str) -> Play {
+    game.parse_opponent(s)
+        .unwrap_or_else(|| panic!("Unexpected input {s}"))
+}
+
+/// What each of [`Game::player_letters`] in the second column stands for, in that order.
+type Mapping = Vec<Play>;
+
+fn parse_with_mapping(game: &Game, s: &str, mapping: &Mapping) -> Play {
+    game.player_letters()
+        .zip(mapping)
+        .find_map(|(c, &p)| s.chars().eq([c]).then_some(p))
+        .unwrap_or_else(|| panic!("Unexpected input {s}"))
+}
+
+fn format_mapping(game: &Game, mapping: &Mapping) -> String {
+    game.player_letters()
+        .zip(mapping)
+        .map(|(c, &p)| format!("{c}={}", game.name(p)))
+        .join(" ")
+}
+
+fn score_with_mapping(game: &Game, games: &[(String, String)], mapping: &Mapping) -> i32 {
     games
         .iter()
         .map(|(opponent, player)| {
-            let opponent = match opponent.as_str() {
-                "A" => Play::Rock,
-                "B" => Play::Paper,
-                "C" => Play::Scissor,
-                x => panic!("Unexpected input {x}"),
-            };
-            let player = match player.as_str() {
-                "X" => Play::Rock,
-                "Y" => Play::Paper,
-                "Z" => Play::Scissor,
-                x => panic!("Unexpected input {x}"),
-            };
-            player.score() + player.get_outcome(&opponent).score()
+            let opponent = parse_opponent(game, opponent);
+            let player = parse_with_mapping(game, player, mapping);
+            game.score(player) + game.get_outcome(player, opponent).score()
         })
         .sum()
 }
 
-fn q2(games: &[(String, String)]) -> i32 {
+#[derive(Debug)]
+struct MappingReport {
+    /// Total score under each possible mapping.
+    scores: Vec<(Mapping, i32)>,
+    best: (Mapping, i32),
+    worst: (Mapping, i32),
+}
+
+/// Score the guide under every way the second column could map onto moves.
+fn analyze_mappings(game: &Game, games: &[(String, String)]) -> MappingReport {
+    let n = game.moves.len();
+    let scores = game
+        .plays()
+        .permutations(n)
+        .map(|mapping| {
+            let score = score_with_mapping(game, games, &mapping);
+            (mapping, score)
+        })
+        .collect_vec();
+    let best = scores.iter().max_by_key(|(_, s)| s).unwrap().clone();
+    let worst = scores.iter().min_by_key(|(_, s)| s).unwrap().clone();
+    MappingReport {
+        scores,
+        best,
+        worst,
+    }
+}
+
+fn q1(game: &Game, games: &[(String, String)]) -> i32 {
+    score_with_mapping(game, games, &game.plays().collect())
+}
+
+fn q2(game: &Game, games: &[(String, String)]) -> anyhow::Result<i32> {
     games
         .iter()
-        .map(|(opponent, expected_outcome)| {
-            let opponent = match opponent.as_str() {
-                "A" => Play::Rock,
-                "B" => Play::Paper,
-                "C" => Play::Scissor,
-                x => panic!("Unexpected input {x}"),
-            };
+        .map(|(opponent, expected_outcome)| -> anyhow::Result<i32> {
+            let opponent = parse_opponent(game, opponent);
             let expected_outcome = match expected_outcome.as_str() {
                 "X" => GameOutcome::Lose,
                 "Y" => GameOutcome::Draw,
                 "Z" => GameOutcome::Win,
                 x => panic!("Unexpected input {x}"),
             };
-            let my_play = match expected_outcome {
-                GameOutcome::Lose => opponent.find_loser(),
-                GameOutcome::Draw => opponent,
-                GameOutcome::Win => opponent.find_winner(),
-            };
-            expected_outcome.score() + my_play.score()
+            let my_play = game.choose(opponent, expected_outcome).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "no move gets a {expected_outcome:?} against {}",
+                    game.name(opponent)
+                )
+            })?;
+            Ok(expected_outcome.score() + game.score(my_play))
         })
         .sum()
 }
+
+mod tournament {
+    use super::{Game, Play};
+    use itertools::Itertools;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashMap;
+
+    /// The moves played so far in a match, from one player's point of view.
+    #[derive(Debug, Default, Clone)]
+    pub(crate) struct History {
+        pub(crate) mine: Vec<Play>,
+        pub(crate) theirs: Vec<Play>,
+    }
+
+    pub(crate) trait Strategy {
+        fn play(&mut self, game: &Game, history: &History, rng: &mut StdRng) -> Play;
+    }
+
+    fn random_play(game: &Game, rng: &mut StdRng) -> Play {
+        Play(rng.gen_range(0..game.moves.len()))
+    }
+
+    /// The best-scoring move that beats `predicted`.
+    fn counter(game: &Game, predicted: Play) -> Play {
+        game.choose(predicted, super::GameOutcome::Win)
+            .unwrap_or(predicted)
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub(crate) struct Random;
+
+    impl Strategy for Random {
+        fn play(&mut self, game: &Game, _: &History, rng: &mut StdRng) -> Play {
+            random_play(game, rng)
+        }
+    }
+
+    /// Counter the opponent's most frequent move so far. Plays randomly until there is a history.
+    #[derive(Debug, Clone, Copy)]
+    pub(crate) struct CounterMostFrequent;
+
+    impl Strategy for CounterMostFrequent {
+        fn play(&mut self, game: &Game, history: &History, rng: &mut StdRng) -> Play {
+            let counts = history.theirs.iter().counts();
+            counts
+                .into_iter()
+                // Break ties by move order so runs are reproducible.
+                .max_by_key(|&(&p, c)| (c, std::cmp::Reverse(p)))
+                .map_or_else(|| random_play(game, rng), |(&p, _)| counter(game, p))
+        }
+    }
+
+    /// Predict the opponent's next move from a first-order Markov chain of their previous moves,
+    /// and counter it. Plays randomly when the opponent's last move has no recorded successor.
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct Markov {
+        transitions: HashMap<Play, HashMap<Play, usize>>,
+    }
+
+    impl Strategy for Markov {
+        fn play(&mut self, game: &Game, history: &History, rng: &mut StdRng) -> Play {
+            if let [.., prev, last] = history.theirs.as_slice() {
+                *self
+                    .transitions
+                    .entry(*prev)
+                    .or_default()
+                    .entry(*last)
+                    .or_default() += 1;
+            }
+            history
+                .theirs
+                .last()
+                .and_then(|last| self.transitions.get(last))
+                .and_then(|next| {
+                    next.iter()
+                        .max_by_key(|&(&p, &c)| (c, std::cmp::Reverse(p)))
+                })
+                .map_or_else(|| random_play(game, rng), |(&p, _)| counter(game, p))
+        }
+    }
+
+    /// Play the strategy guide's moves in order, starting over when it runs out.
+    #[derive(Debug, Clone)]
+    pub(crate) struct FollowGuide(pub(crate) Vec<Play>);
+
+    impl Strategy for FollowGuide {
+        fn play(&mut self, game: &Game, history: &History, rng: &mut StdRng) -> Play {
+            if self.0.is_empty() {
+                return random_play(game, rng);
+            }
+            self.0[history.mine.len() % self.0.len()]
+        }
+    }
+
+    /// A named strategy. Each match builds a fresh instance, so no state leaks between matches.
+    pub(crate) struct Entrant {
+        pub(crate) name: String,
+        pub(crate) make: Box<dyn Fn() -> Box<dyn Strategy>>,
+    }
+
+    impl Entrant {
+        pub(crate) fn new<S: Strategy + Clone + 'static>(name: &str, strategy: S) -> Self {
+            Self {
+                name: name.to_string(),
+                make: Box::new(move || Box::new(strategy.clone())),
+            }
+        }
+    }
+
+    /// Total score of each player over `rounds` rounds.
+    pub(crate) fn play_match(
+        game: &Game,
+        a: &mut dyn Strategy,
+        b: &mut dyn Strategy,
+        rounds: usize,
+        rng: &mut StdRng,
+    ) -> (i32, i32) {
+        // Each player's own view of the match, kept in step.
+        let (mut history_a, mut history_b) = (History::default(), History::default());
+        let mut scores = (0, 0);
+        for _ in 0..rounds {
+            let pa = a.play(game, &history_a, rng);
+            let pb = b.play(game, &history_b, rng);
+            scores.0 += game.score(pa) + game.get_outcome(pa, pb).score();
+            scores.1 += game.score(pb) + game.get_outcome(pb, pa).score();
+            history_a.mine.push(pa);
+            history_a.theirs.push(pb);
+            history_b.mine.push(pb);
+            history_b.theirs.push(pa);
+        }
+        scores
+    }
+
+    /// Summary of one player's match totals.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) struct Distribution {
+        pub(crate) min: i32,
+        pub(crate) max: i32,
+        pub(crate) mean: f64,
+        pub(crate) std_dev: f64,
+    }
+
+    impl Distribution {
+        fn new(samples: &[i32]) -> Self {
+            assert!(!samples.is_empty());
+            let n = samples.len() as f64;
+            let mean = samples.iter().map(|&s| s as f64).sum::<f64>() / n;
+            let variance = samples
+                .iter()
+                .map(|&s| (s as f64 - mean).powi(2))
+                .sum::<f64>()
+                / n;
+            Self {
+                min: *samples.iter().min().unwrap(),
+                max: *samples.iter().max().unwrap(),
+                mean,
+                std_dev: variance.sqrt(),
+            }
+        }
+    }
+
+    impl std::fmt::Display for Distribution {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "mean {:.1} sd {:.1} [{}, {}]",
+                self.mean, self.std_dev, self.min, self.max
+            )
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub(crate) struct Matchup {
+        pub(crate) players: (String, String),
+        pub(crate) scores: (Distribution, Distribution),
+        pub(crate) wins: (usize, usize),
+    }
+
+    /// Play `matches` matches of `rounds` rounds between every pair of entrants. The same seed
+    /// always gives the same report.
+    pub(crate) fn run(
+        game: &Game,
+        entrants: &[Entrant],
+        rounds: usize,
+        matches: usize,
+        seed: u64,
+    ) -> Vec<Matchup> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        entrants
+            .iter()
+            .tuple_combinations()
+            .map(|(a, b)| {
+                let (sa, sb): (Vec<_>, Vec<_>) = (0..matches)
+                    .map(|_| play_match(game, &mut *(a.make)(), &mut *(b.make)(), rounds, &mut rng))
+                    .unzip();
+                Matchup {
+                    players: (a.name.clone(), b.name.clone()),
+                    wins: (
+                        sa.iter().zip(&sb).filter(|(a, b)| a > b).count(),
+                        sa.iter().zip(&sb).filter(|(a, b)| a < b).count(),
+                    ),
+                    scores: (Distribution::new(&sa), Distribution::new(&sb)),
+                }
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        fn entrants(game: &Game) -> Vec<Entrant> {
+            vec![
+                Entrant::new("random", Random),
+                Entrant::new("counter", CounterMostFrequent),
+                Entrant::new("markov", Markov::default()),
+                Entrant::new("rock", FollowGuide(vec![Play(0)])),
+                Entrant::new("cycle", FollowGuide(game.plays().collect())),
+            ]
+        }
+
+        #[test]
+        fn test_seeded_runs_are_reproducible() {
+            let game = Game::rock_paper_scissors();
+            let first = run(&game, &entrants(&game), 50, 5, 42);
+            let second = run(&game, &entrants(&game), 50, 5, 42);
+            assert_eq!(first.len(), 10);
+            for (x, y) in first.iter().zip(&second) {
+                assert_eq!(x.scores, y.scores);
+                assert_eq!(x.wins, y.wins);
+            }
+        }
+
+        #[test]
+        fn test_adaptive_strategies_beat_a_fixed_move() {
+            let game = Game::rock_paper_scissors();
+            let mut rng = StdRng::seed_from_u64(1);
+            for adaptive in [
+                &mut CounterMostFrequent as &mut dyn Strategy,
+                &mut Markov::default(),
+            ] {
+                let (a, b) = play_match(
+                    &game,
+                    adaptive,
+                    &mut FollowGuide(vec![Play(0)]),
+                    100,
+                    &mut rng,
+                );
+                // At most the first couple of rounds are random, then paper beats rock every time.
+                assert!(a >= 98 * (2 + 6), "{a}");
+                assert!(b <= 2 * (1 + 6) + 98, "{b}");
+            }
+        }
+
+        #[test]
+        fn test_markov_predicts_a_cycle() {
+            let game = Game::rock_paper_scissors();
+            let mut rng = StdRng::seed_from_u64(7);
+            let (a, _) = play_match(
+                &game,
+                &mut Markov::default(),
+                &mut FollowGuide(game.plays().collect()),
+                300,
+                &mut rng,
+            );
+            // Once each transition was seen it wins every round.
+            assert!(a >= 290 * 6, "{a}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn guide(lines: &[(&str, &str)]) -> Vec<(String, String)> {
+        lines
+            .iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect()
+    }
+
+    fn sample() -> Vec<(String, String)> {
+        guide(&[("A", "Y"), ("B", "X"), ("C", "Z")])
+    }
+
+    #[test]
+    fn test_sample() {
+        let game = Game::rock_paper_scissors();
+        assert_eq!(q1(&game, &sample()), 15);
+        assert_eq!(q2(&game, &sample()).unwrap(), 12);
+    }
+
+    #[test]
+    fn test_rock_paper_scissors() {
+        let game = Game::rock_paper_scissors();
+        let [rock, paper, scissor] = [0, 1, 2].map(Play);
+        assert_eq!(game.get_outcome(paper, rock), GameOutcome::Win);
+        assert_eq!(game.get_outcome(rock, scissor), GameOutcome::Win);
+        assert_eq!(game.get_outcome(scissor, paper), GameOutcome::Win);
+        assert_eq!(game.get_outcome(rock, paper), GameOutcome::Lose);
+        assert_eq!(game.get_outcome(rock, rock), GameOutcome::Draw);
+        assert_eq!(game.choose(rock, GameOutcome::Win), Some(paper));
+        assert_eq!(game.choose(rock, GameOutcome::Lose), Some(scissor));
+        assert_eq!(game.choose(rock, GameOutcome::Draw), Some(rock));
+    }
+
+    #[test]
+    fn test_rock_paper_scissors_lizard_spock() {
+        let game = Game::rock_paper_scissors_lizard_spock();
+        let play = |name: &str| game.plays().find(|&p| game.name(p) == name).unwrap();
+        let rules = [
+            ("Scissor", "Paper"),
+            ("Paper", "Rock"),
+            ("Rock", "Lizard"),
+            ("Lizard", "Spock"),
+            ("Spock", "Scissor"),
+            ("Scissor", "Lizard"),
+            ("Lizard", "Paper"),
+            ("Paper", "Spock"),
+            ("Spock", "Rock"),
+            ("Rock", "Scissor"),
+        ];
+        for (winner, loser) in rules {
+            assert_eq!(
+                game.get_outcome(play(winner), play(loser)),
+                GameOutcome::Win
+            );
+            assert_eq!(
+                game.get_outcome(play(loser), play(winner)),
+                GameOutcome::Lose
+            );
+        }
+        // Rock is beaten by Paper (2) and Spock (4); pick the better-scoring one.
+        assert_eq!(
+            game.choose(play("Rock"), GameOutcome::Win),
+            Some(play("Spock"))
+        );
+        assert_eq!(game.player_letters().collect::<String>(), "VWXYZ");
+
+        // Spock vaporizes Rock, Rock crushes Lizard, Lizard poisons Spock.
+        let games = guide(&[("A", "Y"), ("E", "V"), ("D", "Z")]);
+        assert_eq!(q1(&game, &games), (4 + 6) + (1 + 6) + (5 + 6));
+        // Beat Rock with Spock, lose to Lizard with Spock, beat Spock with Lizard.
+        let games = guide(&[("A", "Z"), ("E", "X"), ("D", "Z")]);
+        assert_eq!(q2(&game, &games).unwrap(), 10 + 4 + 11);
+    }
+
+    #[test]
+    fn test_custom_game() {
+        // Rock beats everything, the others draw among themselves.
+        let game = Game::parse("Rock 1 beats Paper Scissor\nPaper 2\nScissor 3\n").unwrap();
+        assert_eq!(game.player_letters().collect::<String>(), "XYZ");
+        assert_eq!(game.get_outcome(Play(0), Play(2)), GameOutcome::Win);
+        assert_eq!(game.get_outcome(Play(1), Play(2)), GameOutcome::Draw);
+        assert_eq!(q1(&game, &sample()), 2 + (1 + 6) + (3 + 3));
+        // Nothing beats Rock.
+        let err = q2(&game, &guide(&[("A", "Z")])).unwrap_err();
+        assert_eq!(err.to_string(), "no move gets a Win against Rock");
+        // Lose to Rock and draw with Paper, both with Scissor.
+        assert_eq!(q2(&game, &guide(&[("A", "X"), ("B", "Y")])).unwrap(), 3 + 6);
+    }
+
+    #[test]
+    fn test_invalid_games() {
+        let moves = |n: usize| {
+            (0..n)
+                .map(|i| Move {
+                    name: i.to_string(),
+                    score: 1,
+                })
+                .collect_vec()
+        };
+        assert!(Game::new(moves(26), |_, _| false).is_ok());
+        for n in [0, 27, 300] {
+            assert!(Game::new(moves(n), |_, _| false).is_err(), "{n}");
+        }
+        assert!(Game::cyclic(moves(4)).is_err());
+        assert!(Game::new(moves(2), |_, _| true).is_err());
+        assert!(Game::parse("Rock 1 beats Rock").is_err());
+        assert!(Game::parse("Rock 1 beats Paper").is_err());
+        assert!(Game::parse("Rock x").is_err());
+        assert!(Game::parse("Rock 1 eats Paper\nPaper 2").is_err());
+    }
+
+    #[test]
+    fn test_analyze_mappings() {
+        let game = Game::rock_paper_scissors();
+        let report = analyze_mappings(&game, &sample());
+        assert_eq!(report.scores.len(), 6);
+        assert!(report
+            .scores
+            .contains(&(vec![Play(0), Play(1), Play(2)], 15)));
+        // Every game is a win: X=Scissor beats B, Y=Paper beats A, Z=Rock beats C.
+        assert_eq!(report.best, (vec![Play(2), Play(1), Play(0)], 24));
+        assert!(report.scores.iter().all(|&(_, s)| s >= report.worst.1));
+    }
+}
@@ -1,4 +1,3 @@
-use std::collections::HashSet;
 use std::io::BufRead;
 
 use itertools::Itertools;
@@ -14,6 +13,14 @@ impl Item {
             _ => unreachable!("precondition violated"),
         }
     }
+
+    fn from_score(score: i32) -> Self {
+        match score {
+            1..=26 => Item(b'a' + (score - 1) as u8),
+            27..=52 => Item(b'A' + (score - 27) as u8),
+            _ => unreachable!("precondition violated"),
+        }
+    }
 }
 
 impl std::fmt::Debug for Item {
@@ -35,63 +42,521 @@ impl TryFrom<char> for Item {
     }
 }
 
+/// A set of items as a bitmask: bit `score - 1` is set for each item present.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Hash)]
+struct ItemSet(u64);
+
+impl ItemSet {
+    const ALL: ItemSet = ItemSet((1 << 52) - 1);
+
+    fn insert(&mut self, item: Item) {
+        self.0 |= 1 << (item.score() - 1);
+    }
+
+    fn intersection(self, other: Self) -> Self {
+        ItemSet(self.0 & other.0)
+    }
+
+    fn union(self, other: Self) -> Self {
+        ItemSet(self.0 | other.0)
+    }
+
+    fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    fn iter(&self) -> impl Iterator<Item = Item> {
+        let bits = self.0;
+        (0..52)
+            .filter(move |i| bits & (1 << i) != 0)
+            .map(|i| Item::from_score(i + 1))
+    }
+
+    fn score(&self) -> i32 {
+        self.iter().map(|i| i.score()).sum()
+    }
+}
+
+impl FromIterator<Item> for ItemSet {
+    fn from_iter<T: IntoIterator<Item = Item>>(iter: T) -> Self {
+        let mut out = ItemSet::default();
+        iter.into_iter().for_each(|i| out.insert(i));
+        out
+    }
+}
+
+impl std::fmt::Debug for ItemSet {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{{{}}}", self.iter().map(|i| i.0 as char).join(""))
+    }
+}
+
 struct Sack {
-    items: HashSet<Item>,
-    common: Item,
+    compartments: Vec<ItemSet>,
 }
 
 impl Sack {
-    fn common(&self) -> Item {
-        self.common
+    fn parse(src: &str, compartments: usize) -> anyhow::Result<Self> {
+        anyhow::ensure!(compartments > 0, "a sack needs at least one compartment");
+        let total_len = src.chars().count();
+        if total_len == 0 {
+            anyhow::bail!("empty sack, expected items for {compartments} compartments");
+        }
+        if !total_len.is_multiple_of(compartments) {
+            anyhow::bail!("{total_len} items can't be split into {compartments} compartments");
+        }
+        let items: Vec<Item> = src.chars().map(Item::try_from).try_collect()?;
+        let compartments = items
+            .chunks(total_len / compartments)
+            .map(|c| c.iter().copied().collect())
+            .collect();
+        Ok(Self { compartments })
     }
-    fn items(&self) -> &HashSet<Item> {
-        &self.items
+
+    /// Items found in every compartment.
+    fn common(&self) -> ItemSet {
+        self.compartments
+            .iter()
+            .fold(ItemSet::ALL, |acc, &c| acc.intersection(c))
+    }
+
+    fn items(&self) -> ItemSet {
+        self.compartments
+            .iter()
+            .fold(ItemSet::default(), |acc, &c| acc.union(c))
     }
 }
 
 impl<'a> TryFrom<&'a str> for Sack {
     type Error = anyhow::Error;
     fn try_from(src: &'a str) -> Result<Self, Self::Error> {
-        let total_len = src.len();
-        let mut items = src.chars().map(Item::try_from);
-        let left: HashSet<_> = items.by_ref().take(total_len / 2).try_collect()?;
-        let right: HashSet<_> = items.try_collect()?;
-        let intersection = left.intersection(&right).copied().exactly_one().map_err(|_| anyhow::anyhow!("duplicated items"))?;
-        let mut union = left;
-        union.extend(right.into_iter());
-        Ok(Self{
-            items: union,
-            common: intersection,
-        })
+        Sack::parse(src, 2)
+    }
+}
+
+/// An input that breaks the puzzle's "exactly one" assumptions. Reported, not fatal.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+enum Diagnostic {
+    #[error("sack {sack}: expected exactly one item in every compartment, found {common:?}")]
+    Common { sack: usize, common: ItemSet },
+    #[error("group {group}: expected exactly one badge, found {badges:?}")]
+    Badge { group: usize, badges: ItemSet },
+    #[error("group {group}: expected {expected} sacks, found {found}")]
+    IncompleteGroup {
+        group: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Analyzer {
+    compartments: usize,
+    group_size: usize,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self {
+            compartments: 2,
+            group_size: 3,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Report {
+    /// Items shared by every compartment, per sack.
+    common: Vec<ItemSet>,
+    /// Items shared by every sack, per group of consecutive sacks.
+    badges: Vec<ItemSet>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analyzer {
+    fn parse(&self, src: &str) -> anyhow::Result<Sack> {
+        Sack::parse(src, self.compartments)
+    }
+
+    fn analyze(&self, sacks: &[Sack]) -> Report {
+        let mut report = Report::default();
+        for (i, sack) in sacks.iter().enumerate() {
+            let common = sack.common();
+            if common.len() != 1 {
+                report
+                    .diagnostics
+                    .push(Diagnostic::Common { sack: i, common });
+            }
+            report.common.push(common);
+        }
+        for (i, group) in sacks.chunks(self.group_size).enumerate() {
+            if group.len() != self.group_size {
+                report.diagnostics.push(Diagnostic::IncompleteGroup {
+                    group: i,
+                    expected: self.group_size,
+                    found: group.len(),
+                });
+            }
+            let badges = group
+                .iter()
+                .fold(ItemSet::ALL, |acc, s| acc.intersection(s.items()));
+            if badges.len() != 1 {
+                report
+                    .diagnostics
+                    .push(Diagnostic::Badge { group: i, badges });
+            }
+            report.badges.push(badges);
+        }
+        report
+    }
+}
+
+/// Partitions sacks into groups of `group_size` when the grouping in the input is unknown.
+///
+/// A group is valid when its sacks share exactly one item.
+#[derive(Debug, Clone, Copy)]
+struct GroupingSearch {
+    group_size: usize,
+    /// Stop after this many partitions.
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GroupingOutcome {
+    /// Each partition lists groups of sack indices, sorted within and across groups.
+    partitions: Vec<Vec<Vec<usize>>>,
+    /// Whether the whole search space was covered. If it was and `partitions` is empty, no valid
+    /// grouping exists.
+    exhaustive: bool,
+}
+
+impl GroupingSearch {
+    fn search(&self, sacks: &[Sack]) -> GroupingOutcome {
+        assert!(self.group_size > 0);
+        let mut state = SearchState {
+            items: sacks.iter().map(|s| s.items()).collect(),
+            group_size: self.group_size,
+            limit: self.limit.unwrap_or(usize::MAX),
+            used: vec![false; sacks.len()],
+            assigned: vec![],
+            partitions: vec![],
+        };
+        let exhaustive = if !sacks.len().is_multiple_of(self.group_size) {
+            true
+        } else if state.limit == 0 {
+            false
+        } else {
+            state.next_group()
+        };
+        GroupingOutcome {
+            partitions: state.partitions,
+            exhaustive,
+        }
+    }
+}
+
+struct SearchState {
+    items: Vec<ItemSet>,
+    group_size: usize,
+    limit: usize,
+    used: Vec<bool>,
+    /// Sacks in the order they were grouped: every `group_size` of them form a group, and the
+    /// last ones the group being built.
+    assigned: Vec<usize>,
+    partitions: Vec<Vec<Vec<usize>>>,
+}
+
+impl SearchState {
+    /// Start a group with the first unassigned sack, so each partition is found exactly once.
+    /// Returns false if the search stopped at the limit.
+    fn next_group(&mut self) -> bool {
+        let first = match self.used.iter().position(|&u| !u) {
+            Some(first) => first,
+            None => {
+                let groups = self.assigned.chunks(self.group_size);
+                self.partitions
+                    .push(groups.map(<[usize]>::to_vec).collect());
+                return self.partitions.len() < self.limit;
+            }
+        };
+        self.used[first] = true;
+        self.assigned.push(first);
+        let done = self.extend_group(self.items[first]);
+        self.assigned.pop();
+        self.used[first] = false;
+        done
+    }
+
+    fn extend_group(&mut self, common: ItemSet) -> bool {
+        let group_len = (self.assigned.len() - 1) % self.group_size + 1;
+        if group_len == self.group_size {
+            if common.len() != 1 {
+                return true;
+            }
+            return self.next_group();
+        }
+        let start = self.assigned.last().unwrap() + 1;
+        for next in start..self.items.len() {
+            if self.used[next] {
+                continue;
+            }
+            // Adding sacks only shrinks the intersection, so an empty one is a dead end.
+            let common = common.intersection(self.items[next]);
+            if common.len() == 0 {
+                continue;
+            }
+            self.used[next] = true;
+            self.assigned.push(next);
+            let done = self.extend_group(common);
+            self.assigned.pop();
+            self.used[next] = false;
+            if !done {
+                return false;
+            }
+        }
+        true
     }
 }
 
 fn main() -> anyhow::Result<()> {
+    let (flags, args): (Vec<_>, Vec<_>) =
+        std::env::args().skip(1).partition(|a| a.starts_with("--"));
+    let find_groups = flags.iter().any(|f| f == "--find-groups");
+    let mut args = args.into_iter().map(|s| s.parse::<usize>());
+    let default = Analyzer::default();
+    let analyzer = Analyzer {
+        compartments: args.next().transpose()?.unwrap_or(default.compartments),
+        group_size: args.next().transpose()?.unwrap_or(default.group_size),
+    };
+    anyhow::ensure!(
+        analyzer.compartments > 0,
+        "the number of compartments must be positive"
+    );
+    anyhow::ensure!(analyzer.group_size > 0, "the group size must be positive");
     let stdin = std::io::stdin().lock();
     let sacks: Vec<_> = stdin
         .lines()
         .map(|s| -> anyhow::Result<_> {
             let s = s?;
-            Sack::try_from(s.as_str())
+            analyzer.parse(s.as_str())
         })
         .try_collect()?;
-    let total_common_score = sacks.iter().map(|s| s.common().score()).sum::<i32>();
+    let report = analyzer.analyze(&sacks);
+    for d in &report.diagnostics {
+        eprintln!("warning: {d}");
+    }
+    let total_common_score = report.common.iter().map(|s| s.score()).sum::<i32>();
     println!("q1: {total_common_score}");
-    let badges: Vec<Item> = sacks
-        .into_iter()
-        .map(|s| s.items)
-        .chunks(3)
-        .into_iter()
-        .map(|chunk| {
-            let badge : HashSet<Item> = chunk.reduce(|left, right: HashSet<_>| {
-                left.into_iter().filter(|i| right.contains(&i)).collect()
-            }).expect("It's impossible to have empty chunks");
-            badge.into_iter().exactly_one()
-        })
-        .try_collect()?;
-    let total_group_score = badges.into_iter()
-        .map(|s| s.score())
-        .sum::<i32>();
+    let total_group_score = report.badges.iter().map(|s| s.score()).sum::<i32>();
     println!("q2: {total_group_score}");
+    if find_groups {
+        let outcome = GroupingSearch {
+            group_size: analyzer.group_size,
+            limit: Some(2),
+        }
+        .search(&sacks);
+        match outcome.partitions.as_slice() {
+            [] => println!("no valid grouping"),
+            [partition, rest @ ..] => {
+                let score = partition
+                    .iter()
+                    .map(|g| {
+                        g.iter()
+                            .fold(ItemSet::ALL, |acc, &i| acc.intersection(sacks[i].items()))
+                            .score()
+                    })
+                    .sum::<i32>();
+                let unique = if !rest.is_empty() {
+                    "not unique"
+                } else {
+                    "unique"
+                };
+                println!("found grouping ({unique}) with badge score {score}");
+            }
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("../sample_input.txt");
+
+    fn sacks(analyzer: &Analyzer, src: &str) -> Vec<Sack> {
+        src.lines().map(|l| analyzer.parse(l).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_item_set() {
+        let set: ItemSet = "aZpA".chars().map(|c| Item::try_from(c).unwrap()).collect();
+        assert_eq!(set.len(), 4);
+        assert_eq!(
+            set.iter().collect_vec(),
+            [Item(b'a'), Item(b'p'), Item(b'A'), Item(b'Z')]
+        );
+        assert_eq!(set.score(), 1 + 16 + 27 + 52);
+    }
+
+    #[test]
+    fn test_sample() {
+        let analyzer = Analyzer::default();
+        let report = analyzer.analyze(&sacks(&analyzer, SAMPLE));
+        assert_eq!(report.diagnostics, vec![]);
+        assert_eq!(report.common.iter().map(|s| s.score()).sum::<i32>(), 157);
+        assert_eq!(report.badges.iter().map(|s| s.score()).sum::<i32>(), 70);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = Sack::parse("", 2).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "empty sack, expected items for 2 compartments"
+        );
+        let err = Sack::parse("abcde", 2).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "5 items can't be split into 2 compartments"
+        );
+        assert!(Sack::parse("ab1d", 2).is_err());
+        let err = Sack::parse("abcd", 0).err().unwrap();
+        assert_eq!(err.to_string(), "a sack needs at least one compartment");
+    }
+
+    #[test]
+    fn test_configurable_sizes() {
+        let analyzer = Analyzer {
+            compartments: 3,
+            group_size: 2,
+        };
+        let report = analyzer.analyze(&sacks(&analyzer, "abcAbdEbf\nxbybbz"));
+        assert_eq!(
+            report.common,
+            ["b", "b"].map(|s| s.chars().map(|c| Item::try_from(c).unwrap()).collect())
+        );
+        assert_eq!(report.badges.len(), 1);
+        assert_eq!(report.badges[0].iter().collect_vec(), [Item(b'b')]);
+    }
+
+    #[test]
+    fn test_grouping_search_finds_sample_groups() {
+        let analyzer = Analyzer::default();
+        let mut lines = SAMPLE.lines().collect_vec();
+        // Interleave the two groups.
+        lines = [0, 3, 1, 4, 2, 5].map(|i| lines[i]).to_vec();
+        let outcome = GroupingSearch {
+            group_size: 3,
+            limit: None,
+        }
+        .search(&sacks(&analyzer, &lines.join("\n")));
+        assert!(outcome.exhaustive);
+        // The puzzle's grouping, plus one more that also happens to share exactly one item.
+        assert_eq!(
+            outcome.partitions,
+            vec![
+                vec![vec![0, 2, 4], vec![1, 3, 5]],
+                vec![vec![0, 3, 5], vec![1, 2, 4]]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_grouping_search_enumerates_every_partition() {
+        let analyzer = Analyzer {
+            compartments: 1,
+            group_size: 2,
+        };
+        // Every pair shares exactly `a`.
+        let sacks = sacks(&analyzer, "ab\nac\nad\nae");
+        let outcome = GroupingSearch {
+            group_size: 2,
+            limit: None,
+        }
+        .search(&sacks);
+        assert!(outcome.exhaustive);
+        assert_eq!(
+            outcome.partitions,
+            vec![
+                vec![vec![0, 1], vec![2, 3]],
+                vec![vec![0, 2], vec![1, 3]],
+                vec![vec![0, 3], vec![1, 2]],
+            ]
+        );
+        let limited = GroupingSearch {
+            group_size: 2,
+            limit: Some(1),
+        }
+        .search(&sacks);
+        assert!(!limited.exhaustive);
+        assert_eq!(limited.partitions.len(), 1);
+        let none = GroupingSearch {
+            group_size: 2,
+            limit: Some(0),
+        }
+        .search(&sacks);
+        assert!(!none.exhaustive);
+        assert!(none.partitions.is_empty());
+    }
+
+    #[test]
+    fn test_grouping_search_proves_none_exists() {
+        let analyzer = Analyzer {
+            compartments: 1,
+            group_size: 2,
+        };
+        let search = GroupingSearch {
+            group_size: 2,
+            limit: None,
+        };
+        // `ab` shares two items with `ab`, and nothing with `cd`.
+        let none = search.search(&sacks(&analyzer, "ab\nab\ncd\nce"));
+        assert!(none.exhaustive);
+        assert!(none.partitions.is_empty());
+        let uneven = search.search(&sacks(&analyzer, "ab\nac\nad"));
+        assert!(uneven.exhaustive);
+        assert!(uneven.partitions.is_empty());
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let analyzer = Analyzer::default();
+        let report = analyzer.analyze(&sacks(&analyzer, "abab\nabcd\nabxy\nab"));
+        let ab: ItemSet = [Item(b'a'), Item(b'b')].into_iter().collect();
+        assert_eq!(
+            report.diagnostics,
+            vec![
+                Diagnostic::Common {
+                    sack: 0,
+                    common: ab
+                },
+                Diagnostic::Common {
+                    sack: 1,
+                    common: ItemSet::default()
+                },
+                Diagnostic::Common {
+                    sack: 2,
+                    common: ItemSet::default()
+                },
+                Diagnostic::Common {
+                    sack: 3,
+                    common: ItemSet::default()
+                },
+                Diagnostic::Badge {
+                    group: 0,
+                    badges: ab
+                },
+                Diagnostic::IncompleteGroup {
+                    group: 1,
+                    expected: 3,
+                    found: 1
+                },
+                Diagnostic::Badge {
+                    group: 1,
+                    badges: ab
+                },
+            ]
+        );
+    }
+}
@@ -1,12 +1,13 @@
+use std::fmt;
 use std::io::BufRead;
-use std::str::FromStr;
 
 use itertools::Itertools;
 
 use nom::{
     IResult,
     character::complete::{digit1, char},
-    combinator::{map, map_res, all_consuming},
+    combinator::{map, map_res, all_consuming, verify},
+    multi::separated_list1,
     sequence::separated_pair,
 };
 
@@ -19,52 +20,284 @@ fn parse_i32(s: &str) -> IResult<&str, i32> {
 
 fn parse_assignment(input: &str) -> IResult<&str, Assignment> {
     map(
-        separated_pair(parse_i32, char('-'), parse_i32),
+        verify(
+            separated_pair(parse_i32, char('-'), parse_i32),
+            |&(l, r)| l <= r,
+        ),
         |(l, r)| Assignment(l, r)
     )(input)
 }
 
-fn parse_assignment_pair(input: &str) -> IResult<&str, AssignmentPair> {
+fn parse_assignment_group(input: &str) -> IResult<&str, AssignmentGroup> {
     map(
-        separated_pair(parse_assignment, char(','), parse_assignment),
-        |(l, r)| AssignmentPair(l, r)
+        verify(
+            separated_list1(char(','), parse_assignment),
+            |v: &Vec<_>| v.len() >= 2,
+        ),
+        AssignmentGroup
     )(input)
 }
 
-#[derive(Debug, Copy, Clone)]
+/// An inclusive range of sections.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 struct Assignment(i32, i32);
 
-#[derive(Debug, Copy, Clone)]
-struct AssignmentPair(Assignment, Assignment);
+impl fmt::Display for Assignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.0, self.1)
+    }
+}
+
+/// How one assignment sits relative to another.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Relation {
+    Equal,
+    Contains,
+    ContainedBy,
+    /// Starts first and ends inside the other.
+    OverlapsLeft,
+    /// Starts inside the other and ends after it.
+    OverlapsRight,
+    /// No shared section, but no gap between them either.
+    Adjacent,
+    Disjoint,
+}
+
+impl Relation {
+    fn is_containment(self) -> bool {
+        matches!(self, Relation::Equal | Relation::Contains | Relation::ContainedBy)
+    }
+
+    fn is_overlap(self) -> bool {
+        self.is_containment() || matches!(self, Relation::OverlapsLeft | Relation::OverlapsRight)
+    }
+}
+
+impl Assignment {
+    fn relation(self, other: Self) -> Relation {
+        let Assignment(lmin, lmax) = self;
+        let Assignment(rmin, rmax) = other;
+        if (lmin, lmax) == (rmin, rmax) {
+            Relation::Equal
+        } else if lmin <= rmin && lmax >= rmax {
+            Relation::Contains
+        } else if lmin >= rmin && lmax <= rmax {
+            Relation::ContainedBy
+        } else if lmin < rmin && rmin <= lmax {
+            Relation::OverlapsLeft
+        } else if rmin < lmin && lmin <= rmax {
+            Relation::OverlapsRight
+        } else if lmax.checked_add(1) == Some(rmin) || rmax.checked_add(1) == Some(lmin) {
+            Relation::Adjacent
+        } else {
+            Relation::Disjoint
+        }
+    }
+}
+
+/// The assignments of one line: two elves in the puzzle, but any number of at least two parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AssignmentGroup(Vec<Assignment>);
+
+impl AssignmentGroup {
+    fn relations(&self) -> impl Iterator<Item = Relation> + '_ {
+        self.0.iter().tuple_combinations().map(|(&l, &r)| l.relation(r))
+    }
+}
+
+/// Result of sweeping over every assignment at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Coverage {
+    /// Most assignments covering any single section.
+    max_concurrent: usize,
+    /// Gaps between the lowest and highest assigned section.
+    uncovered: Vec<Assignment>,
+    /// Sections assigned to more than one elf.
+    covered_more_than_once: Vec<Assignment>,
+}
+
+/// Close the run that ended right before `pos`, or open one starting at `pos`.
+fn update_runs(ranges: &mut Vec<(i64, i64)>, was_in: bool, is_in: bool, pos: i64) {
+    match (was_in, is_in) {
+        (true, false) => {
+            if let Some(last) = ranges.last_mut() {
+                last.1 = pos - 1;
+            }
+        }
+        (false, true) => ranges.push((pos, pos)),
+        _ => (),
+    }
+}
+
+fn coverage<'a>(assignments: impl IntoIterator<Item = &'a Assignment>) -> Coverage {
+    // +1 where an assignment starts, -1 right after it ends, which may be past `i32::MAX`.
+    let events = assignments
+        .into_iter()
+        .flat_map(|&Assignment(l, r)| [(i64::from(l), 1), (i64::from(r) + 1, -1)])
+        .sorted()
+        .collect_vec();
+    let mut max_concurrent = 0;
+    let mut uncovered = vec![];
+    let mut covered_more_than_once = vec![];
+    let mut active = 0;
+    for (pos, group) in &events.iter().group_by(|(pos, _)| *pos) {
+        let before = active;
+        active += group.map(|(_, delta)| delta).sum::<i32>();
+        max_concurrent = max_concurrent.max(active as usize);
+        update_runs(&mut uncovered, before == 0, active == 0, pos);
+        update_runs(&mut covered_more_than_once, before >= 2, active >= 2, pos);
+    }
+    // Nothing is active after the last event, but that isn't a gap between assignments.
+    uncovered.pop();
+    // Every run left lies between assigned sections.
+    let to_assignments = |runs: Vec<(i64, i64)>| {
+        runs.into_iter()
+            .map(|(l, r)| Assignment(l as i32, r as i32))
+            .collect()
+    };
+    Coverage {
+        max_concurrent,
+        uncovered: to_assignments(uncovered),
+        covered_more_than_once: to_assignments(covered_more_than_once),
+    }
+}
 
 fn main() -> anyhow::Result<()> {
+    const USAGE: &str = "usage: aoc_22_04 [--coverage] < input";
+    let mut show_coverage = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--coverage" => show_coverage = true,
+            _ => anyhow::bail!(USAGE),
+        }
+    }
     let stdin = std::io::stdin().lock();
     let input: Vec<_> = stdin.lines()
         .map(|line| -> anyhow::Result<_> {
             let line = line?;
-            let (_, this_pair) =
-                all_consuming(parse_assignment_pair)(&line)
+            let (_, this_group) =
+                all_consuming(parse_assignment_group)(&line)
                 .map_err(|e| e.to_owned())?;
-            Ok(this_pair)
+            Ok(this_group)
         }).try_collect()?;
-    println!("q1: {}", q1(input.iter().copied()));
-    println!("q2: {}", q2(input.into_iter()));
+    println!("q1: {}", q1(input.iter().cloned()));
+    println!("q2: {}", q2(input.iter().cloned()));
+    if show_coverage {
+        let cov = coverage(input.iter().flat_map(|g| &g.0));
+        let show = |ranges: &[Assignment]| match ranges {
+            [] => "none".to_string(),
+            _ => ranges.iter().join(", "),
+        };
+        println!("max concurrent: {}", cov.max_concurrent);
+        println!("uncovered: {}", show(&cov.uncovered));
+        println!("covered more than once: {}", show(&cov.covered_more_than_once));
+    }
     Ok(())
 }
 
-fn q1(args: impl IntoIterator<Item=AssignmentPair>) -> usize {
+/// Groups where some assignment fully contains another.
+fn q1(args: impl IntoIterator<Item=AssignmentGroup>) -> usize {
     args.into_iter()
-        .filter(|src| {
-            let AssignmentPair(Assignment(lmin, lmax), Assignment(rmin, rmax)) = *src;
-            (lmin <= rmin && lmax >= rmax) || (lmin >= rmin && lmax <= rmax)
-        }).count()
+        .filter(|src| src.relations().any(Relation::is_containment))
+        .count()
+}
 
+/// Groups where some assignments overlap.
+fn q2(args: impl IntoIterator<Item=AssignmentGroup>) -> usize {
+    args.into_iter()
+        .filter(|src| src.relations().any(Relation::is_overlap))
+        .count()
 }
 
-fn q2(args: impl IntoIterator<Item=AssignmentPair>) -> usize {
-    use std::cmp::{max, min};
-    args.into_iter().filter(|&s| {
-        let AssignmentPair(Assignment(lmin, lmax), Assignment(rmin, rmax)) = s;
-        max(lmin, rmin) <= min(lmax, rmax)
-    }).count()
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(s: &str) -> Vec<AssignmentGroup> {
+        s.lines()
+            .map(|l| all_consuming(parse_assignment_group)(l).unwrap().1)
+            .collect()
+    }
+
+    #[test]
+    fn test_sample() {
+        let input = parse(include_str!("../sample_input.txt"));
+        assert_eq!(q1(input.clone()), 2);
+        assert_eq!(q2(input), 4);
+    }
+
+    #[test]
+    fn test_relation() {
+        use Relation::*;
+        let a = Assignment(3, 6);
+        let cases = [
+            (Assignment(3, 6), Equal),
+            (Assignment(4, 5), Contains),
+            (Assignment(3, 5), Contains),
+            (Assignment(2, 6), ContainedBy),
+            (Assignment(5, 9), OverlapsLeft),
+            (Assignment(6, 6), Contains),
+            (Assignment(1, 4), OverlapsRight),
+            (Assignment(7, 9), Adjacent),
+            (Assignment(1, 2), Adjacent),
+            (Assignment(8, 9), Disjoint),
+        ];
+        for (b, relation) in cases {
+            assert_eq!(a.relation(b), relation, "{a:?} {b:?}");
+        }
+        assert_eq!(Assignment(5, 9).relation(a), OverlapsRight);
+        let max = Assignment(i32::MAX, i32::MAX);
+        assert_eq!(max.relation(Assignment(1, 2)), Disjoint);
+        assert_eq!(max.relation(Assignment(1, i32::MAX - 1)), Adjacent);
+        assert_eq!(Assignment(1, i32::MAX - 1).relation(max), Adjacent);
+    }
+
+    #[test]
+    fn test_groups_of_more_than_two() {
+        let input = parse("1-2,4-5,6-9\n1-3,5-6,2-2\n1-1,3-3,4-5,5-7");
+        assert_eq!(input[0].0.len(), 3);
+        assert_eq!(q1(input.clone()), 1);
+        assert_eq!(q2(input), 2);
+        assert!(all_consuming(parse_assignment_group)("1-2").is_err());
+    }
+
+    #[test]
+    fn test_reversed_range() {
+        assert!(all_consuming(parse_assignment_group)("5-2,1-3").is_err());
+        assert!(all_consuming(parse_assignment_group)("1-3,5-2").is_err());
+        assert_eq!(parse("2-2,1-3")[0].0[0], Assignment(2, 2));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let input = parse(include_str!("../sample_input.txt"));
+        let cov = coverage(input.iter().flat_map(|g| &g.0));
+        assert_eq!(
+            cov,
+            Coverage {
+                max_concurrent: 8,
+                uncovered: vec![],
+                covered_more_than_once: vec![Assignment(2, 8)],
+            }
+        );
+        let cov = coverage(&[Assignment(1, 3), Assignment(6, 7), Assignment(7, 9), Assignment(12, 12)]);
+        assert_eq!(
+            cov,
+            Coverage {
+                max_concurrent: 2,
+                uncovered: vec![Assignment(4, 5), Assignment(10, 11)],
+                covered_more_than_once: vec![Assignment(7, 7)],
+            }
+        );
+        assert_eq!(cov.uncovered.iter().join(", "), "4-5, 10-11");
+        let cov = coverage(&[Assignment(1, i32::MAX), Assignment(i32::MAX - 1, i32::MAX)]);
+        assert_eq!(
+            cov,
+            Coverage {
+                max_concurrent: 2,
+                uncovered: vec![],
+                covered_more_than_once: vec![Assignment(i32::MAX - 1, i32::MAX)],
+            }
+        );
+    }
 }
@@ -1,9 +1,12 @@
+use crane::{CapacityLimited, Crane, CrateMover9000, CrateMover9001, MoveError, Rearrangement};
+use itertools::Itertools;
+use planner::Planner;
 use std::io::Read;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 struct Item(char);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct Stacks(Vec<Vec<Item>>);
 
 mod parse_regex {
@@ -11,7 +14,7 @@ mod parse_regex {
     use once_cell::sync::Lazy;
     use regex::Regex;
 
-    use super::{Item, Stacks};
+    use super::{Item, Move, Stacks};
     static ITEM_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[(.)\]").unwrap());
 
     static DIGITS_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d+").unwrap());
@@ -36,8 +39,13 @@ mod parse_regex {
         let mut st = vec![vec![]; stacks_cnt + 1];
         for line in rows_rev {
             for i in 0..stacks_cnt {
+                // Lines may stop short of the last stacks instead of being padded.
                 let beg = i * SECT_LEN;
-                let s = &line[beg..beg + SECT_LEN - 1].trim();
+                let end = (beg + SECT_LEN - 1).min(line.len());
+                let s = line
+                    .get(beg.min(end)..end)
+                    .ok_or_else(|| anyhow::anyhow!("bad drawing line: {line:?}"))?
+                    .trim();
                 if !s.is_empty() {
                     st[i + 1].push(parse_item(s)?);
                 }
@@ -46,21 +54,21 @@ mod parse_regex {
         Ok(Stacks(st))
     }
 
-    fn parse_moves(s: &str) -> anyhow::Result<(usize, usize, usize)> {
+    fn parse_moves(s: &str) -> anyhow::Result<Move> {
         let bail = || anyhow::anyhow!("Can't extract moves from {s}");
-        let (x, y, z) = DIGITS_REGEX
+        let (count, src, dst) = DIGITS_REGEX
             .find_iter(s)
             .map(|s| s.as_str().parse::<usize>().map_err(|_| bail()))
             .collect_tuple()
             .ok_or_else(&bail)?;
-        let x = x?;
-        let y = y?;
-        let z = z?;
-        Ok((x, y, z))
+        Ok(Move {
+            count: count?,
+            src: src?,
+            dst: dst?,
+        })
     }
 
-    #[allow(unused)]
-    pub(crate) fn parse(s: &str) -> anyhow::Result<(Stacks, Vec<(usize, usize, usize)>)> {
+    pub(crate) fn parse(s: &str) -> anyhow::Result<(Stacks, Vec<Move>)> {
         let mut lines = s.lines();
         let first_section = lines.by_ref().take_while(|s| !s.is_empty()).collect_vec();
         let stacks = parse_stacks(first_section)?;
@@ -73,14 +81,14 @@ mod parse_nom {
     use nom::{
         branch::alt,
         bytes::complete::tag,
-        character::complete::{anychar, char, newline, satisfy, u8 as parse_u8},
-        combinator::{all_consuming, map, value},
-        multi::{many0_count, separated_list1},
-        sequence::{delimited, preceded, separated_pair, tuple},
+        character::complete::{anychar, char, digit1, newline, satisfy, space0},
+        combinator::{all_consuming, map, map_res, opt, value},
+        multi::{many0, many0_count, many1_count, separated_list1},
+        sequence::{delimited, preceded, terminated, tuple},
         IResult,
     };
 
-    use super::{Item, Stacks};
+    use super::{Item, Move, Stacks};
 
     fn parse_item(s: &str) -> IResult<&str, Item> {
         map(delimited(char('['), anychar, char(']')), Item)(s)
@@ -97,21 +105,12 @@ mod parse_nom {
 
         // returns the total number of stacks
         fn parse_bottom_row(s: &str) -> IResult<&str, usize> {
-            fn parse_stack_tag(s: &str) -> IResult<&str, ()> {
-                delimited(
-                    char(' '),
-                    value((), satisfy(|c| c.is_ascii_digit())), // the tag is unused
-                    char(' '),
-                )(s)
-            }
-            map(separated_list1(char(' '), parse_stack_tag), |v| v.len())(s)
+            // The tags are unused, and may be wider than one digit.
+            many1_count(delimited(space0, digit1, space0))(s)
         }
 
-        let (input, (rows, len)) = separated_pair(
-            separated_list1(newline, parse_row),
-            newline,
-            parse_bottom_row,
-        )(s)?;
+        let (input, (rows, len)) =
+            tuple((many0(terminated(parse_row, newline)), parse_bottom_row))(s)?;
         let mut items = vec![vec![]; len + 1];
         for item_list in rows.into_iter().rev() {
             item_list.into_iter().enumerate().for_each(|(i, item)| {
@@ -123,25 +122,197 @@ mod parse_nom {
         Ok((input, Stacks(items)))
     }
 
-    fn parse_moves(s: &str) -> IResult<&str, Vec<(usize, usize, usize)>> {
-        fn parse_row(s: &str) -> IResult<&str, (usize, usize, usize)> {
+    fn parse_usize(s: &str) -> IResult<&str, usize> {
+        map_res(digit1, str::parse)(s)
+    }
+
+    fn parse_moves(s: &str) -> IResult<&str, Vec<Move>> {
+        fn parse_row(s: &str) -> IResult<&str, Move> {
             map(
                 tuple((
-                    preceded(many0_count(satisfy(|c| !c.is_ascii_digit())), parse_u8),
-                    preceded(many0_count(satisfy(|c| !c.is_ascii_digit())), parse_u8),
-                    preceded(many0_count(satisfy(|c| !c.is_ascii_digit())), parse_u8),
+                    preceded(many0_count(satisfy(|c| !c.is_ascii_digit())), parse_usize),
+                    preceded(many0_count(satisfy(|c| !c.is_ascii_digit())), parse_usize),
+                    preceded(many0_count(satisfy(|c| !c.is_ascii_digit())), parse_usize),
                 )),
-                |(x, y, z)| (x as usize, y as usize, z as usize),
+                |(count, src, dst)| Move { count, src, dst },
             )(s)
         }
         separated_list1(newline, parse_row)(s)
     }
 
-    pub(crate) fn parse(s: &str) -> anyhow::Result<(Stacks, Vec<(usize, usize, usize)>)> {
+    pub(crate) fn parse(s: &str) -> anyhow::Result<(Stacks, Vec<Move>)> {
+        let s = s.trim_end();
+        // The moves are optional, so that a drawing on its own parses too.
+        let (_, (stacks, moves)) =
+            all_consuming(tuple((parse_stack, opt(preceded(newline, parse_moves)))))(s)
+                .map_err(|e| e.to_owned())?;
+        Ok((stacks, moves.unwrap_or_default()))
+    }
+}
+
+/// The available input parsers. They should agree on every well-formed input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Parser {
+    Regex,
+    Nom,
+}
+
+impl Parser {
+    fn parse(self, s: &str) -> anyhow::Result<(Stacks, Vec<Move>)> {
         let s = s.trim_end();
-        let (_, out) = all_consuming(separated_pair(parse_stack, newline, parse_moves))(s)
-            .map_err(|e| e.to_owned())?;
-        Ok(out)
+        match self {
+            Parser::Regex => parse_regex::parse(s),
+            Parser::Nom => parse_nom::parse(s),
+        }
+    }
+}
+
+impl std::str::FromStr for Parser {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "regex" => Ok(Parser::Regex),
+            "nom" => Ok(Parser::Nom),
+            _ => anyhow::bail!("unknown parser {s}, expected regex or nom"),
+        }
+    }
+}
+
+/// One `move count from src to dst` instruction. Stacks are numbered from 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Move {
+    count: usize,
+    src: usize,
+    dst: usize,
+}
+
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.src, self.dst)
+    }
+}
+
+mod crane {
+    use super::{Item, Move, Stacks};
+
+    #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+    pub(crate) enum MoveError {
+        #[error("{mv}: there is no stack {stack}")]
+        NoSuchStack { mv: Move, stack: usize },
+        #[error("{mv}: source and destination are the same stack")]
+        SameStack { mv: Move },
+        #[error("{mv}: stack {} only has {available} crates", mv.src)]
+        NotEnoughCrates { mv: Move, available: usize },
+        #[error("{mv}: the crane can lift at most {capacity} crates")]
+        OverCapacity { mv: Move, capacity: usize },
+    }
+
+    /// A crane model: how crates lifted off one stack land on another.
+    pub(crate) trait Crane {
+        /// `lifted` holds the moved crates bottom first, as they were on the source stack. Reorder
+        /// them into the order they end up in on the destination stack, bottom first.
+        fn arrange(&self, lifted: &mut Vec<Item>);
+
+        /// Reject moves this crane can't do. Stack bounds are checked separately.
+        fn validate(&self, _mv: Move) -> Result<(), MoveError> {
+            Ok(())
+        }
+    }
+
+    /// Moves one crate at a time, so the lifted crates end up reversed.
+    #[derive(Copy, Clone, Debug)]
+    pub(crate) struct CrateMover9000;
+
+    impl Crane for CrateMover9000 {
+        fn arrange(&self, lifted: &mut Vec<Item>) {
+            lifted.reverse();
+        }
+    }
+
+    /// Moves all crates at once, keeping their order.
+    #[derive(Copy, Clone, Debug)]
+    pub(crate) struct CrateMover9001;
+
+    impl Crane for CrateMover9001 {
+        fn arrange(&self, _lifted: &mut Vec<Item>) {}
+    }
+
+    /// Wraps a crane to refuse moves of more than `capacity` crates.
+    #[derive(Copy, Clone, Debug)]
+    pub(crate) struct CapacityLimited<C> {
+        pub(crate) crane: C,
+        pub(crate) capacity: usize,
+    }
+
+    impl<C: Crane> Crane for CapacityLimited<C> {
+        fn arrange(&self, lifted: &mut Vec<Item>) {
+            self.crane.arrange(lifted)
+        }
+
+        fn validate(&self, mv: Move) -> Result<(), MoveError> {
+            if mv.count > self.capacity {
+                return Err(MoveError::OverCapacity {
+                    mv,
+                    capacity: self.capacity,
+                });
+            }
+            self.crane.validate(mv)
+        }
+    }
+
+    /// Stacks being rearranged by a crane, with undo/redo.
+    pub(crate) struct Rearrangement<C> {
+        pub(crate) stacks: Stacks,
+        crane: C,
+        /// Applied moves with the crates they lifted, most recent last.
+        done: Vec<(Move, Vec<Item>)>,
+        undone: Vec<Move>,
+    }
+
+    impl<C: Crane> Rearrangement<C> {
+        pub(crate) fn new(stacks: Stacks, crane: C) -> Self {
+            Self {
+                stacks,
+                crane,
+                done: vec![],
+                undone: vec![],
+            }
+        }
+
+        /// Apply a new move. This discards anything that could be redone.
+        pub(crate) fn apply(&mut self, mv: Move) -> Result<(), MoveError> {
+            let lifted = self.stacks.apply(&self.crane, mv)?;
+            self.done.push((mv, lifted));
+            self.undone.clear();
+            Ok(())
+        }
+
+        #[cfg(test)]
+        pub(crate) fn undo(&mut self) -> Option<Move> {
+            let (mv, lifted) = self.done.pop()?;
+            let (src, dst) = self.stacks.nth_mut(mv.src, mv.dst);
+            dst.truncate(dst.len() - mv.count);
+            src.extend(lifted);
+            self.undone.push(mv);
+            Some(mv)
+        }
+
+        #[cfg(test)]
+        pub(crate) fn redo(&mut self) -> Option<Move> {
+            let mv = self.undone.pop()?;
+            let lifted = self
+                .stacks
+                .apply(&self.crane, mv)
+                .expect("a move that was applied before still applies after undo");
+            self.done.push((mv, lifted));
+            Some(mv)
+        }
+
+        #[cfg(test)]
+        pub(crate) fn history(&self) -> impl Iterator<Item = Move> + '_ {
+            self.done.iter().map(|(mv, _)| *mv)
+        }
     }
 }
 
@@ -163,46 +334,660 @@ impl Stacks {
         }
     }
 
-    fn move_items_one_by_one(&mut self, count: usize, src: usize, dst: usize) {
-        let (src, dst) = self.nth_mut(src, dst);
-        dst.extend(src.drain(src.len() - count..).rev());
+    fn validate(&self, mv: Move) -> Result<(), MoveError> {
+        for stack in [mv.src, mv.dst] {
+            // Stack 0 is a placeholder so that stacks can be indexed by their number.
+            if stack == 0 || stack >= self.0.len() {
+                return Err(MoveError::NoSuchStack { mv, stack });
+            }
+        }
+        if mv.src == mv.dst {
+            return Err(MoveError::SameStack { mv });
+        }
+        let available = self.0[mv.src].len();
+        if available < mv.count {
+            return Err(MoveError::NotEnoughCrates { mv, available });
+        }
+        Ok(())
     }
 
-    fn move_items_consecutive(&mut self, count: usize, src: usize, dst: usize) {
-        let (src, dst) = self.nth_mut(src, dst);
-        dst.extend(src.drain(src.len() - count..));
+    /// Apply a move, returning the lifted crates in their original order.
+    fn apply(&mut self, crane: &impl Crane, mv: Move) -> Result<Vec<Item>, MoveError> {
+        self.validate(mv)?;
+        crane.validate(mv)?;
+        let (src, dst) = self.nth_mut(mv.src, mv.dst);
+        let lifted = src.split_off(src.len() - mv.count);
+        let mut landed = lifted.clone();
+        crane.arrange(&mut landed);
+        dst.extend(landed);
+        Ok(lifted)
     }
 
+    /// The top crate of every stack, with a space for each empty stack.
     fn extract_tops(&self) -> String {
         self.0
             .iter()
             .skip(1)
-            .map(|x| x.last().map(|item| item.0).unwrap())
+            .map(|x| x.last().map_or(' ', |item| item.0))
             .collect()
     }
 }
 
+/// Draws the stacks the way the puzzle input does, numbered row at the bottom. Every row is padded
+/// to the full width, so printing a parsed drawing gives back the same text.
+impl std::fmt::Display for Stacks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stacks = &self.0[1..];
+        let height = stacks.iter().map(Vec::len).max().unwrap_or(0);
+        for row in (0..height).rev() {
+            let cells = stacks.iter().map(|stack| match stack.get(row) {
+                Some(Item(c)) => format!("[{c}]"),
+                None => "   ".to_string(),
+            });
+            writeln!(f, "{}", cells.format(" "))?;
+        }
+        let labels = (1..=stacks.len()).map(|i| format!("{:^3}", i));
+        write!(f, "{}", labels.format(" "))
+    }
+}
+
+mod planner {
+    use std::collections::HashSet;
+
+    use itertools::Itertools;
+
+    use super::crane::Crane;
+    use super::{Move, Stacks};
+
+    #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+    pub(crate) enum PlanError {
+        #[error("the start has {start} stacks but the target has {target}")]
+        StackCount { start: usize, target: usize },
+        #[error("the start and the target hold different crates")]
+        DifferentCrates,
+        #[error("no plan of at most {0} moves")]
+        TooLong(usize),
+    }
+
+    /// A lower bound on the moves still needed. A stack with crates above the part that already
+    /// matches the target is the source of at least one more move, and a stack missing crates is
+    /// the destination of at least one. Every move has one of each.
+    fn heuristic(stacks: &Stacks, target: &Stacks) -> usize {
+        let (mut sources, mut destinations) = (0, 0);
+        for (s, t) in stacks.0.iter().zip(&target.0) {
+            let matching = s.iter().zip(t).take_while(|(a, b)| a == b).count();
+            sources += usize::from(matching < s.len());
+            destinations += usize::from(matching < t.len());
+        }
+        sources.max(destinations)
+    }
+
+    /// Finds a shortest sequence of moves between two configurations with IDA*.
+    pub(crate) struct Planner<C> {
+        crane: C,
+        pub(crate) max_moves: usize,
+    }
+
+    impl<C: Crane> Planner<C> {
+        pub(crate) fn new(crane: C) -> Self {
+            Self {
+                crane,
+                max_moves: 20,
+            }
+        }
+
+        pub(crate) fn plan(&self, start: &Stacks, target: &Stacks) -> Result<Vec<Move>, PlanError> {
+            if start.0.len() != target.0.len() {
+                return Err(PlanError::StackCount {
+                    start: start.0.len() - 1,
+                    target: target.0.len() - 1,
+                });
+            }
+            let crates = |s: &Stacks| {
+                s.0.iter()
+                    .flatten()
+                    .map(|item| item.0)
+                    .sorted()
+                    .collect_vec()
+            };
+            if crates(start) != crates(target) {
+                return Err(PlanError::DifferentCrates);
+            }
+            let mut path = vec![];
+            let mut on_path = HashSet::from([start.clone()]);
+            let mut bound = heuristic(start, target);
+            while bound <= self.max_moves {
+                match self.search(start, target, bound, &mut path, &mut on_path) {
+                    Ok(()) => return Ok(path),
+                    Err(next_bound) => bound = next_bound,
+                }
+            }
+            Err(PlanError::TooLong(self.max_moves))
+        }
+
+        /// Depth-first search for the target within `bound` moves in total. On failure, returns the
+        /// smallest estimate that went over the bound, which is the bound to try next.
+        fn search(
+            &self,
+            stacks: &Stacks,
+            target: &Stacks,
+            bound: usize,
+            path: &mut Vec<Move>,
+            on_path: &mut HashSet<Stacks>,
+        ) -> Result<(), usize> {
+            let estimate = path.len() + heuristic(stacks, target);
+            if estimate > bound {
+                return Err(estimate);
+            }
+            if stacks == target {
+                return Ok(());
+            }
+            let mut next_bound = usize::MAX;
+            for mv in self.candidate_moves(stacks) {
+                let mut next = stacks.clone();
+                if next.apply(&self.crane, mv).is_err() || on_path.contains(&next) {
+                    continue;
+                }
+                on_path.insert(next.clone());
+                path.push(mv);
+                match self.search(&next, target, bound, path, on_path) {
+                    Ok(()) => return Ok(()),
+                    Err(b) => next_bound = next_bound.min(b),
+                }
+                path.pop();
+                on_path.remove(&next);
+            }
+            Err(next_bound)
+        }
+
+        fn candidate_moves(&self, stacks: &Stacks) -> Vec<Move> {
+            let n = stacks.0.len();
+            (1..n)
+                .cartesian_product(1..n)
+                .filter(|(src, dst)| src != dst)
+                .flat_map(|(src, dst)| {
+                    (1..=stacks.0[src].len()).map(move |count| Move { count, src, dst })
+                })
+                .collect()
+        }
+    }
+}
+
 fn main() -> anyhow::Result<()> {
+    const USAGE: &str = "usage: aoc_22_05 [--parser regex|nom] [--capacity N] \
+        [--animate [9000|9001]] [--plan TARGET_DRAWING [9000|9001]] < input";
+    let mut parser = Parser::Regex;
+    let mut capacity = None;
+    let mut animate_with = None;
+    let mut plan_for = None;
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--parser" => parser = args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?.parse()?,
+            "--capacity" => {
+                capacity = Some(args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?.parse()?);
+            }
+            "--animate" => {
+                let model = args.next_if(|arg| !arg.starts_with("--"));
+                animate_with = Some(model.unwrap_or_else(|| "9000".to_string()));
+            }
+            "--plan" => {
+                let target = args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                let model = args.next_if(|arg| !arg.starts_with("--"));
+                plan_for = Some((target, model.unwrap_or_else(|| "9000".to_string())));
+            }
+            _ => anyhow::bail!(USAGE),
+        }
+    }
+
     let stdin = std::io::stdin();
     let mut input = String::new();
     stdin.lock().read_to_string(&mut input)?;
-    let s = input.trim_end();
-    let (stacks, moves) = parse_regex::parse(&s)?;
-    println!("q1: {}", q1(stacks.clone(), &moves));
-    println!("q2: {}", q2(stacks, &moves));
+    let (stacks, moves) = parser.parse(&input)?;
+    let limit = capacity.unwrap_or(usize::MAX);
+    if let Some((target, model)) = plan_for {
+        // Only the drawings matter here, the plan replaces the moves.
+        let (target, _) = parser.parse(&std::fs::read_to_string(target)?)?;
+        let plan = match model.as_str() {
+            "9000" => Planner::new(CapacityLimited {
+                crane: CrateMover9000,
+                capacity: limit,
+            })
+            .plan(&stacks, &target)?,
+            "9001" => Planner::new(CapacityLimited {
+                crane: CrateMover9001,
+                capacity: limit,
+            })
+            .plan(&stacks, &target)?,
+            model => anyhow::bail!("unknown crane model {model}"),
+        };
+        println!("{}", plan.iter().join("\n"));
+        return Ok(());
+    }
+    match animate_with.as_deref() {
+        None => (),
+        Some("9000") => {
+            let crane = CapacityLimited {
+                crane: CrateMover9000,
+                capacity: limit,
+            };
+            animate(crane, stacks.clone(), &moves)?
+        }
+        Some("9001") => {
+            let crane = CapacityLimited {
+                crane: CrateMover9001,
+                capacity: limit,
+            };
+            animate(crane, stacks.clone(), &moves)?
+        }
+        Some(model) => anyhow::bail!("unknown crane model {model}"),
+    }
+    let (a1, a2) = match capacity {
+        None => (q1(stacks.clone(), &moves)?, q2(stacks, &moves)?),
+        Some(capacity) => {
+            let crane = CapacityLimited {
+                crane: CrateMover9000,
+                capacity,
+            };
+            let a1 = rearrange(crane, stacks.clone(), &moves)?;
+            let crane = CapacityLimited {
+                crane: CrateMover9001,
+                capacity,
+            };
+            (a1, rearrange(crane, stacks, &moves)?)
+        }
+    };
+    println!("q1: {a1}");
+    println!("q2: {a2}");
     Ok(())
 }
 
-fn q1(mut s: Stacks, moves: &[(usize, usize, usize)]) -> String {
-    for (count, src, dst) in moves.iter().copied() {
-        s.move_items_one_by_one(count, src, dst);
+/// Print the drawing before the first move and after each one.
+fn animate(crane: impl Crane, s: Stacks, moves: &[Move]) -> Result<(), MoveError> {
+    let mut r = Rearrangement::new(s, crane);
+    println!("{}\n", r.stacks);
+    for &mv in moves {
+        r.apply(mv)?;
+        println!("{mv}\n{}\n", r.stacks);
     }
-    s.extract_tops()
+    Ok(())
+}
+
+fn rearrange(crane: impl Crane, s: Stacks, moves: &[Move]) -> Result<String, MoveError> {
+    let mut r = Rearrangement::new(s, crane);
+    for &mv in moves {
+        r.apply(mv)?;
+    }
+    Ok(r.stacks.extract_tops())
+}
+
+fn q1(s: Stacks, moves: &[Move]) -> Result<String, MoveError> {
+    rearrange(CrateMover9000, s, moves)
+}
+
+fn q2(s: Stacks, moves: &[Move]) -> Result<String, MoveError> {
+    rearrange(CrateMover9001, s, moves)
 }
 
-fn q2(mut s: Stacks, moves: &[(usize, usize, usize)]) -> String {
-    for (count, src, dst) in moves.iter().copied() {
-        s.move_items_consecutive(count, src, dst);
+#[cfg(test)]
+mod test {
+    use super::crane::*;
+    use super::planner::*;
+    use super::*;
+    use proptest::prelude::*;
+
+    const SAMPLE: &str = include_str!("../sample_input.txt");
+
+    fn sample() -> (Stacks, Vec<Move>) {
+        Parser::Regex.parse(SAMPLE).unwrap()
+    }
+
+    fn stacks_of(s: &Stacks) -> Vec<String> {
+        s.0.iter()
+            .skip(1)
+            .map(|v| v.iter().map(|i| i.0).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_sample() {
+        let (stacks, moves) = sample();
+        assert_eq!(q1(stacks.clone(), &moves).unwrap(), "CMZ");
+        assert_eq!(q2(stacks, &moves).unwrap(), "MCD");
+    }
+
+    #[test]
+    fn test_unpadded_drawing() {
+        let (stacks, _) = assert_parsers_agree("[A]\n[B] [C]\n 1   2   3\n\nmove 1 from 1 to 3");
+        assert_eq!(stacks_of(&stacks), ["BA", "C", ""]);
+        assert!(Parser::Regex.parse("[A] [é]\n 1   2\n").is_err());
+    }
+
+    #[test]
+    fn test_emptied_stack() {
+        let (stacks, moves) = Parser::Regex
+            .parse("[A] [B]\n 1   2 \n\nmove 1 from 1 to 2")
+            .unwrap();
+        assert_eq!(q1(stacks.clone(), &moves).unwrap(), " A");
+        assert_eq!(q2(stacks, &moves).unwrap(), " A");
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for input in [SAMPLE, include_str!("../input.txt")] {
+            let input = input.trim_end();
+            let (stacks, moves) = parse_regex::parse(input).unwrap();
+            let drawing = input.split("\n\n").next().unwrap();
+            assert_eq!(stacks.to_string(), drawing);
+            let printed = format!("{stacks}\n\n{}", moves.iter().join("\n"));
+            assert_eq!(printed, input);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let item = |c: &str| c.chars().map(Item).collect_vec();
+        let mut stacks = vec![vec![]];
+        stacks.extend(["A", "", "BC"].map(item));
+        stacks.extend(std::iter::repeat_with(Vec::new).take(7));
+        stacks.push(item("D"));
+        let stacks = Stacks(stacks);
+        let expected = [
+            "        [C]                                ",
+            "[A]     [B]                             [D]",
+            " 1   2   3   4   5   6   7   8   9  10  11 ",
+        ]
+        .join("\n");
+        assert_eq!(stacks.to_string(), expected);
+        let (parsed, _) = parse_regex::parse(&format!("{expected}\n\nmove 1 from 1 to 2")).unwrap();
+        assert_eq!(parsed, stacks);
+    }
+
+    fn assert_parsers_agree(input: &str) -> (Stacks, Vec<Move>) {
+        let [regex, nom] = [Parser::Regex, Parser::Nom].map(|p| p.parse(input).unwrap());
+        assert_eq!(regex, nom, "{input}");
+        regex
+    }
+
+    #[test]
+    fn test_parsers_agree() {
+        assert_parsers_agree(SAMPLE);
+        assert_parsers_agree(include_str!("../input.txt"));
+        let (stacks, moves) = assert_parsers_agree(" 1   2  \n\nmove 100 from 10 to 2\n");
+        assert_eq!(stacks.0.len(), 3);
+        assert_eq!(
+            moves[0],
+            Move {
+                count: 100,
+                src: 10,
+                dst: 2
+            }
+        );
+    }
+
+    fn arb_stacks() -> impl Strategy<Value = Stacks> {
+        let stack = prop::collection::vec(prop::char::range('A', 'Z').prop_map(Item), 0..8);
+        prop::collection::vec(stack, 1..15).prop_map(|stacks| {
+            let mut all = vec![vec![]];
+            all.extend(stacks);
+            Stacks(all)
+        })
+    }
+
+    fn arb_moves() -> impl Strategy<Value = Vec<Move>> {
+        let mv = (0..2000usize, 1..200usize, 1..200usize).prop_map(|(count, src, dst)| Move {
+            count,
+            src,
+            dst,
+        });
+        prop::collection::vec(mv, 1..10)
+    }
+
+    proptest! {
+        #[test]
+        fn test_parsers_agree_on_random_input(
+            stacks in arb_stacks(),
+            moves in arb_moves(),
+            padded in any::<bool>(),
+        ) {
+            let drawing = stacks.to_string();
+            let drawing = if padded {
+                drawing
+            } else {
+                drawing.lines().map(str::trim_end).join("\n")
+            };
+            let input = format!("{drawing}\n\n{}\n", moves.iter().join("\n"));
+            let parsed = assert_parsers_agree(&input);
+            prop_assert_eq!(parsed, (stacks, moves));
+        }
+    }
+
+    /// Replay a plan the way the puzzle input would be: as text, through the parser and `q1`/`q2`.
+    fn replay(crane: impl Crane, start: &Stacks, plan: &[Move]) -> Stacks {
+        let input = format!("{start}\n\n{}", plan.iter().join("\n"));
+        let (stacks, moves) = Parser::Regex.parse(&input).unwrap();
+        let mut r = Rearrangement::new(stacks, crane);
+        for mv in moves {
+            r.apply(mv).unwrap();
+        }
+        r.stacks
+    }
+
+    /// Plain breadth-first search, to check that the planner's plans are shortest.
+    fn shortest_plan_len(crane: &impl Crane, start: &Stacks, target: &Stacks) -> usize {
+        let mut seen = std::collections::HashSet::from([start.clone()]);
+        let mut frontier = vec![start.clone()];
+        for len in 0.. {
+            if frontier.contains(target) {
+                return len;
+            }
+            let mut next_frontier = vec![];
+            for stacks in frontier {
+                let n = stacks.0.len();
+                for (src, dst) in (1..n).cartesian_product(1..n) {
+                    for count in 1..=stacks.0[src].len() {
+                        let mut next = stacks.clone();
+                        if next.apply(crane, Move { count, src, dst }).is_ok()
+                            && seen.insert(next.clone())
+                        {
+                            next_frontier.push(next);
+                        }
+                    }
+                }
+            }
+            frontier = next_frontier;
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn test_plan_emptying_a_stack() {
+        let item = |c: &str| c.chars().map(Item).collect_vec();
+        let start = Stacks(vec![vec![], item("A"), item("B"), item("")]);
+        let target = Stacks(vec![vec![], item(""), item("BA"), item("")]);
+        // Both parsers read a drawing without moves.
+        for parser in [Parser::Regex, Parser::Nom] {
+            assert_eq!(
+                parser.parse(&target.to_string()).unwrap(),
+                (target.clone(), vec![])
+            );
+        }
+        for (plan, q) in [
+            (
+                Planner::new(CrateMover9000).plan(&start, &target),
+                q1 as fn(Stacks, &[Move]) -> _,
+            ),
+            (Planner::new(CrateMover9001).plan(&start, &target), q2),
+        ] {
+            let plan = plan.unwrap();
+            assert_eq!(plan.len(), 1);
+            let text = format!("{start}\n\n{}", plan.iter().join("\n"));
+            let (stacks, moves) = Parser::Nom.parse(&text).unwrap();
+            assert_eq!(q(stacks, &moves).unwrap(), " A ");
+        }
+    }
+
+    #[test]
+    fn test_plan_sample() {
+        let (start, moves) = sample();
+        let mut r = Rearrangement::new(start.clone(), CrateMover9000);
+        moves.iter().for_each(|&mv| r.apply(mv).unwrap());
+        let target = r.stacks;
+        let plan = Planner::new(CrateMover9000).plan(&start, &target).unwrap();
+        assert!(plan.len() <= moves.len());
+        assert_eq!(
+            plan.len(),
+            shortest_plan_len(&CrateMover9000, &start, &target)
+        );
+        assert_eq!(replay(CrateMover9000, &start, &plan), target);
+        let text = format!("{start}\n\n{}", plan.iter().join("\n"));
+        assert_eq!(
+            q1(start.clone(), &Parser::Nom.parse(&text).unwrap().1).unwrap(),
+            "CMZ"
+        );
+
+        let plan = Planner::new(CrateMover9001).plan(&start, &target).unwrap();
+        assert_eq!(
+            plan.len(),
+            shortest_plan_len(&CrateMover9001, &start, &target)
+        );
+        assert_eq!(replay(CrateMover9001, &start, &plan), target);
+    }
+
+    #[test]
+    fn test_plan_errors() {
+        let (start, _) = sample();
+        let (target, _) = Parser::Regex.parse("[Z] [N] [D]\n 1   2   3 ").unwrap();
+        assert_eq!(
+            Planner::new(CrateMover9000).plan(&start, &target),
+            Err(PlanError::DifferentCrates)
+        );
+        let (target, _) = Parser::Regex.parse("[Z] [N]\n 1   2 ").unwrap();
+        assert_eq!(
+            Planner::new(CrateMover9000).plan(&start, &target),
+            Err(PlanError::StackCount {
+                start: 3,
+                target: 2
+            })
+        );
+        let (target, _) = Parser::Regex
+            .parse("[C]        \n[D]        \n[M]        \n[N]        \n[P]        \n[Z]        \n 1   2   3 ")
+            .unwrap();
+        let mut planner = Planner::new(CapacityLimited {
+            crane: CrateMover9001,
+            capacity: 1,
+        });
+        planner.max_moves = 2;
+        assert_eq!(planner.plan(&start, &target), Err(PlanError::TooLong(2)));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(30))]
+        #[test]
+        fn test_plan_is_shortest(
+            crates in prop::collection::vec((prop::char::range('A', 'D'), 1..4usize, 1..4usize), 0..6)
+        ) {
+            // Each crate goes on one stack at the start and maybe another one in the target.
+            let mut start = Stacks(vec![vec![]; 4]);
+            let mut target = Stacks(vec![vec![]; 4]);
+            for (c, from, to) in crates {
+                start.0[from].push(Item(c));
+                target.0[to].push(Item(c));
+            }
+            let plan = Planner::new(CrateMover9000).plan(&start, &target).unwrap();
+            prop_assert_eq!(plan.len(), shortest_plan_len(&CrateMover9000, &start, &target));
+            prop_assert_eq!(replay(CrateMover9000, &start, &plan), target);
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        let (stacks, _) = sample();
+        let mut r = Rearrangement::new(stacks, CrateMover9000);
+        let mv = |count, src, dst| Move { count, src, dst };
+        assert_eq!(
+            r.apply(mv(1, 0, 1)),
+            Err(MoveError::NoSuchStack {
+                mv: mv(1, 0, 1),
+                stack: 0
+            })
+        );
+        assert_eq!(
+            r.apply(mv(1, 1, 4)),
+            Err(MoveError::NoSuchStack {
+                mv: mv(1, 1, 4),
+                stack: 4
+            })
+        );
+        assert_eq!(
+            r.apply(mv(1, 2, 2)),
+            Err(MoveError::SameStack { mv: mv(1, 2, 2) })
+        );
+        assert_eq!(
+            r.apply(mv(3, 1, 2)),
+            Err(MoveError::NotEnoughCrates {
+                mv: mv(3, 1, 2),
+                available: 2
+            })
+        );
+        // Nothing changed.
+        assert_eq!(stacks_of(&r.stacks), ["ZN", "MCD", "P"]);
+        assert_eq!(
+            MoveError::NotEnoughCrates {
+                mv: mv(3, 1, 2),
+                available: 2
+            }
+            .to_string(),
+            "move 3 from 1 to 2: stack 1 only has 2 crates"
+        );
+    }
+
+    #[test]
+    fn test_capacity_limited() {
+        let (stacks, moves) = sample();
+        let crane = CapacityLimited {
+            crane: CrateMover9001,
+            capacity: 2,
+        };
+        assert_eq!(
+            rearrange(crane, stacks.clone(), &moves),
+            Err(MoveError::OverCapacity {
+                mv: moves[1],
+                capacity: 2
+            })
+        );
+        let crane = CapacityLimited {
+            crane: CrateMover9001,
+            capacity: 3,
+        };
+        assert_eq!(rearrange(crane, stacks, &moves).unwrap(), "MCD");
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let (stacks, moves) = sample();
+        let mut r = Rearrangement::new(stacks, CrateMover9000);
+        let mut states = vec![stacks_of(&r.stacks)];
+        for &mv in &moves {
+            r.apply(mv).unwrap();
+            states.push(stacks_of(&r.stacks));
+        }
+        for (i, &mv) in moves.iter().enumerate().rev() {
+            assert_eq!(r.undo(), Some(mv));
+            assert_eq!(stacks_of(&r.stacks), states[i]);
+        }
+        assert_eq!(r.undo(), None);
+        assert_eq!(r.redo(), Some(moves[0]));
+        assert_eq!(r.redo(), Some(moves[1]));
+        assert_eq!(stacks_of(&r.stacks), states[2]);
+        // A new move drops the redo history.
+        r.apply(Move {
+            count: 1,
+            src: 3,
+            dst: 1,
+        })
+        .unwrap();
+        assert_eq!(r.redo(), None);
+        assert_eq!(r.history().count(), 3);
     }
-    s.extract_tops()
 }
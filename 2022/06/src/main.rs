@@ -1,25 +1,323 @@
-use itertools::Itertools;
-use std::io::Read;
-fn find_substring_without_duplicates(s: &str, len: usize) -> Option<usize> {
-    s.as_bytes()
-        .windows(len)
-        .enumerate()
-        .find(|&(_, w)| w.iter().all_unique())
-        .map(|(x, _)| x + len)
+use marker::{AllDistinct, AtMostDuplicates, ContainsAll, Detector, MarkerScanner, Predicate};
+use std::io::{BufReader, Read};
+
+mod marker {
+    use std::collections::VecDeque;
+    use std::io::{BufReader, Bytes, Read};
+
+    /// Byte counts of the sliding window, updated in O(1) per step.
+    #[derive(Clone, Debug)]
+    pub(crate) struct Window {
+        counts: [usize; 256],
+        distinct: usize,
+        bytes: VecDeque<u8>,
+    }
+
+    impl Window {
+        fn new(len: usize) -> Self {
+            Self {
+                counts: [0; 256],
+                distinct: 0,
+                bytes: VecDeque::with_capacity(len),
+            }
+        }
+
+        fn push(&mut self, b: u8) {
+            if self.counts[b as usize] == 0 {
+                self.distinct += 1;
+            }
+            self.counts[b as usize] += 1;
+            self.bytes.push_back(b);
+        }
+
+        fn pop(&mut self) {
+            if let Some(b) = self.bytes.pop_front() {
+                self.counts[b as usize] -= 1;
+                if self.counts[b as usize] == 0 {
+                    self.distinct -= 1;
+                }
+            }
+        }
+
+        pub(crate) fn len(&self) -> usize {
+            self.bytes.len()
+        }
+
+        pub(crate) fn count(&self, b: u8) -> usize {
+            self.counts[b as usize]
+        }
+
+        /// Number of bytes that repeat an earlier byte in the window.
+        pub(crate) fn duplicates(&self) -> usize {
+            self.len() - self.distinct
+        }
+    }
+
+    pub(crate) trait Predicate {
+        fn matches(&self, window: &Window) -> bool;
+    }
+
+    impl<P: Predicate + ?Sized> Predicate for Box<P> {
+        fn matches(&self, window: &Window) -> bool {
+            (**self).matches(window)
+        }
+    }
+
+    /// Every byte in the window is different. This is the puzzle's marker.
+    #[derive(Copy, Clone, Debug)]
+    pub(crate) struct AllDistinct;
+
+    impl Predicate for AllDistinct {
+        fn matches(&self, window: &Window) -> bool {
+            window.duplicates() == 0
+        }
+    }
+
+    #[derive(Copy, Clone, Debug)]
+    pub(crate) struct AtMostDuplicates(pub(crate) usize);
+
+    impl Predicate for AtMostDuplicates {
+        fn matches(&self, window: &Window) -> bool {
+            window.duplicates() <= self.0
+        }
+    }
+
+    /// The window contains every byte of the given alphabet at least once.
+    ///
+    /// Costs O(alphabet size) per step.
+    #[derive(Clone, Debug)]
+    pub(crate) struct ContainsAll(pub(crate) Vec<u8>);
+
+    impl Predicate for ContainsAll {
+        fn matches(&self, window: &Window) -> bool {
+            self.0.iter().all(|&b| window.count(b) > 0)
+        }
+    }
+
+    /// A window of the input that satisfied the predicate. `end` is the puzzle's answer: the number
+    /// of bytes read when the marker completes.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub(crate) struct Match {
+        pub(crate) start: usize,
+        pub(crate) end: usize,
+    }
+
+    /// Checks windows of length `len` that satisfy a predicate, one byte at a time.
+    pub(crate) struct Detector<P> {
+        window: Window,
+        len: usize,
+        predicate: P,
+        consumed: usize,
+    }
+
+    impl<P: Predicate> Detector<P> {
+        pub(crate) fn new(len: usize, predicate: P) -> Self {
+            assert!(len > 0);
+            Self {
+                window: Window::new(len),
+                len,
+                predicate,
+                consumed: 0,
+            }
+        }
+
+        /// Feeds the next byte, returning the match that ends with it if any.
+        pub(crate) fn push(&mut self, b: u8) -> Option<Match> {
+            self.consumed += 1;
+            if self.window.len() == self.len {
+                self.window.pop();
+            }
+            self.window.push(b);
+            if self.window.len() == self.len && self.predicate.matches(&self.window) {
+                Some(Match {
+                    start: self.consumed - self.len,
+                    end: self.consumed,
+                })
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Scans a byte stream for windows of length `len` that satisfy a predicate, yielding each
+    /// match in order.
+    pub(crate) struct MarkerScanner<R: Read, P> {
+        input: Bytes<BufReader<R>>,
+        detector: Detector<P>,
+    }
+
+    impl<R: Read, P: Predicate> MarkerScanner<R, P> {
+        pub(crate) fn new(input: R, len: usize, predicate: P) -> Self {
+            Self {
+                input: BufReader::new(input).bytes(),
+                detector: Detector::new(len, predicate),
+            }
+        }
+    }
+
+    impl<R: Read, P: Predicate> Iterator for MarkerScanner<R, P> {
+        type Item = std::io::Result<Match>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            loop {
+                let b = match self.input.next()? {
+                    Ok(b) => b,
+                    Err(e) => return Some(Err(e)),
+                };
+                if let Some(m) = self.detector.push(b) {
+                    return Some(Ok(m));
+                }
+            }
+        }
+    }
+}
+
+/// Ends of the first all-distinct window of each length, found in a single pass over `input`.
+fn find_markers<const N: usize>(
+    input: impl Read,
+    lens: [usize; N],
+) -> std::io::Result<[Option<usize>; N]> {
+    let mut detectors = lens.map(|len| Detector::new(len, AllDistinct));
+    let mut ends = [None; N];
+    for b in BufReader::new(input).bytes() {
+        let b = b?;
+        for (detector, end) in detectors.iter_mut().zip(&mut ends) {
+            if end.is_none() {
+                *end = detector.push(b).map(|m| m.end);
+            }
+        }
+        if ends.iter().all(Option::is_some) {
+            break;
+        }
+    }
+    Ok(ends)
 }
 
 fn main() -> anyhow::Result<()> {
-    let mut s = String::new();
-    std::io::stdin().read_to_string(&mut s)?;
-    println!(
-        "q1: {}",
-        find_substring_without_duplicates(&s, 4)
-            .ok_or_else(|| anyhow::anyhow!("Can't find answer"))?
-    );
-    println!(
-        "q2: {}",
-        find_substring_without_duplicates(&s, 14)
-            .ok_or_else(|| anyhow::anyhow!("Can't find answer"))?
-    );
+    const USAGE: &str = "usage: aoc_22_06 [--len N [--at-most-duplicates M | --contains BYTES] \
+                         [--all]] < input";
+    let mut len = None;
+    let mut predicate: Box<dyn Predicate> = Box::new(AllDistinct);
+    let mut all = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow::anyhow!(USAGE));
+        match arg.as_str() {
+            "--len" => len = Some(value()?.parse::<usize>()?),
+            "--at-most-duplicates" => {
+                predicate = Box::new(AtMostDuplicates(value()?.parse()?));
+            }
+            "--contains" => predicate = Box::new(ContainsAll(value()?.into_bytes())),
+            "--all" => all = true,
+            _ => anyhow::bail!(USAGE),
+        }
+    }
+    let stdin = std::io::stdin().lock();
+    let Some(len) = len else {
+        let [q1, q2] = find_markers(stdin, [4, 14])?;
+        let answer = |end: Option<usize>| end.ok_or_else(|| anyhow::anyhow!("Can't find answer"));
+        println!("q1: {}", answer(q1)?);
+        println!("q2: {}", answer(q2)?);
+        return Ok(());
+    };
+    anyhow::ensure!(len > 0, "the window length must be positive");
+    let mut scanner = MarkerScanner::new(stdin, len, predicate);
+    if all {
+        for m in scanner {
+            let m = m?;
+            println!("{}..{}", m.start, m.end);
+        }
+    } else {
+        let m = scanner
+            .next()
+            .transpose()?
+            .ok_or_else(|| anyhow::anyhow!("Can't find answer"))?;
+        println!("{}", m.end);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::marker::*;
+    use super::*;
+    use itertools::Itertools;
+
+    const SAMPLES: [(&str, usize, usize); 4] = [
+        ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+        ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+        ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 10, 29),
+        ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26),
+    ];
+
+    fn naive(s: &str, len: usize, predicate: impl Fn(&[u8]) -> bool) -> Vec<Match> {
+        s.as_bytes()
+            .windows(len)
+            .enumerate()
+            .filter(|&(_, w)| predicate(w))
+            .map(|(start, _)| Match {
+                start,
+                end: start + len,
+            })
+            .collect()
+    }
+
+    fn scan(s: &str, len: usize, predicate: impl Predicate) -> Vec<Match> {
+        MarkerScanner::new(s.as_bytes(), len, predicate)
+            .collect::<std::io::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_samples() {
+        for (s, q1, q2) in SAMPLES {
+            assert_eq!(
+                find_markers(s.as_bytes(), [4, 14]).unwrap(),
+                [Some(q1), Some(q2)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_all_matches() {
+        for (s, _, _) in SAMPLES {
+            for len in 1..8 {
+                assert_eq!(
+                    scan(s, len, AllDistinct),
+                    naive(s, len, |w| w.iter().all_unique())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_at_most_duplicates() {
+        for (s, _, _) in SAMPLES {
+            for dups in 0..3 {
+                assert_eq!(
+                    scan(s, 6, AtMostDuplicates(dups)),
+                    naive(s, 6, |w| w.len() - w.iter().unique().count() <= dups)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_contains_all() {
+        for (s, _, _) in SAMPLES {
+            assert_eq!(
+                scan(s, 5, ContainsAll(b"jv".to_vec())),
+                naive(s, 5, |w| w.contains(&b'j') && w.contains(&b'v'))
+            );
+        }
+    }
+
+    #[test]
+    fn test_too_short() {
+        assert_eq!(find_markers("abc".as_bytes(), [4]).unwrap(), [None]);
+        assert_eq!(
+            find_markers("abcd".as_bytes(), [4, 14]).unwrap(),
+            [Some(4), None]
+        );
+    }
+}
@@ -19,7 +19,7 @@ fn read_input(x: impl BufRead) -> anyhow::Result<Vec<Vec<u8>>> {
         .try_collect()
 }
 
-fn to_visible(input: &Vec<Vec<u8>>) -> Vec<Vec<bool>> {
+fn to_visible(input: &[Vec<u8>]) -> Vec<Vec<bool>> {
     let height = input.len();
     assert!(height > 0);
     let width = input[0].len();
@@ -49,23 +49,82 @@ fn to_visible(input: &Vec<Vec<u8>>) -> Vec<Vec<bool>> {
     }
 
     fn iter_for_col<'a>(
-        x: &'a Vec<Vec<u8>>,
-        out: &'a mut Vec<Vec<bool>>,
+        x: &'a [Vec<u8>],
+        out: &'a mut [Vec<bool>],
         i: usize,
-    ) -> impl Iterator<Item = (u8, &'a mut bool)> + std::iter::DoubleEndedIterator {
+    ) -> impl DoubleEndedIterator<Item = (u8, &'a mut bool)> {
         x.iter()
             .map(move |v| v[i])
             .zip(out.iter_mut().map(move |v| &mut v[i]))
     }
     for i in 1..width - 1 {
-        tag(iter_for_col(&input, &mut output, i));
-        tag(iter_for_col(&input, &mut output, i).rev());
+        tag(iter_for_col(input, &mut output, i));
+        tag(iter_for_col(input, &mut output, i).rev());
     }
     output
 }
 
 fn main() -> anyhow::Result<()> {
+    const USAGE: &str = "usage: aoc_22_08 [--visibility] [--top K] [--heatmap [--pgm]] \
+        [--sight ROW,COL ROW,COL] < input";
+    let mut show_visibility = false;
+    let mut top = None;
+    let mut heatmap = false;
+    let mut pgm = false;
+    let mut sight = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--visibility" => show_visibility = true,
+            "--top" => {
+                let k = args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                top = Some(k.parse::<usize>()?);
+            }
+            "--heatmap" => heatmap = true,
+            "--pgm" => pgm = true,
+            "--sight" => {
+                let mut tree = || -> anyhow::Result<(usize, usize)> {
+                    let arg = args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                    let (i, j) = arg.split_once(',').ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                    Ok((i.parse()?, j.parse()?))
+                };
+                sight = Some((tree()?, tree()?));
+            }
+            _ => anyhow::bail!(USAGE),
+        }
+    }
     let input = read_input(std::io::stdin().lock())?;
+
+    if show_visibility {
+        for dir in analysis::Direction::ALL {
+            let visible = analysis::visible_from(&input, dir);
+            let count = visible.iter().flatten().filter(|&&x| x).count();
+            println!("visible from {dir:?}: {count}");
+        }
+    }
+    if heatmap {
+        let scores = analysis::scenic_scores_fast(&input);
+        if pgm {
+            return Ok(analysis::heatmap_pgm(
+                &scores,
+                &mut std::io::stdout().lock(),
+            )?);
+        }
+        println!("{}", analysis::heatmap_ascii(&scores));
+    }
+    if let Some(k) = top {
+        let scores = analysis::scenic_scores_fast(&input);
+        for ((i, j), score) in analysis::top_k(&scores, k) {
+            println!("({i}, {j}): {score}");
+        }
+    }
+    if let Some((a, b)) = sight {
+        let inside = |(i, j): (usize, usize)| input.get(i).is_some_and(|row| j < row.len());
+        anyhow::ensure!(inside(a) && inside(b), "no tree at {a:?} or {b:?}");
+        let seen = analysis::line_of_sight(&input, a, b);
+        println!("line of sight from {a:?} to {b:?}: {seen}");
+    }
+
     let visibles = to_visible(&input);
     let q1_res = visibles.iter().flatten().filter(|&&x| x).count();
     println!("q1: {q1_res}");
@@ -74,7 +133,8 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn q2(input: Vec<Vec<u8>>) -> usize {
+#[cfg(test)]
+fn scenic_score_at(input: &[Vec<u8>], i: usize, j: usize) -> usize {
     fn scenic_score(input: impl Iterator<Item = u8>, h: u8) -> usize {
         let mut out = 0;
         for x in input {
@@ -85,18 +145,343 @@ fn q2(input: Vec<Vec<u8>>) -> usize {
         }
         out
     }
-    let scenic_score_for_idx = |i: usize, j: usize| -> usize {
-        let h = input[i][j];
-        let left = scenic_score(input[i][..j].iter().copied().rev(), h);
-        let right = scenic_score(input[i][j + 1..].iter().copied(), h);
-        let up = scenic_score(input[..i].iter().rev().map(|v| v[j]), h);
-        let down = scenic_score(input[i + 1..].iter().map(|v| v[j]), h);
-        left * right * up * down
-    };
-    let height = input.len();
-    let width = input[0].len();
-    (1..height - 1)
-        .cartesian_product(1..width - 1)
-        .map(|(i, j)| scenic_score_for_idx(i, j))
-        .max().unwrap_or(0)
+    let h = input[i][j];
+    let left = scenic_score(input[i][..j].iter().copied().rev(), h);
+    let right = scenic_score(input[i][j + 1..].iter().copied(), h);
+    let up = scenic_score(input[..i].iter().rev().map(|v| v[j]), h);
+    let down = scenic_score(input[i + 1..].iter().map(|v| v[j]), h);
+    left * right * up * down
+}
+
+fn q2(input: Vec<Vec<u8>>) -> usize {
+    analysis::scenic_scores_fast(&input)
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(0)
+}
+
+mod analysis {
+    use itertools::Itertools;
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub(crate) enum Direction {
+        North,
+        NorthEast,
+        East,
+        SouthEast,
+        South,
+        SouthWest,
+        West,
+        NorthWest,
+    }
+
+    impl Direction {
+        pub(crate) const ALL: [Direction; 8] = [
+            Direction::North,
+            Direction::NorthEast,
+            Direction::East,
+            Direction::SouthEast,
+            Direction::South,
+            Direction::SouthWest,
+            Direction::West,
+            Direction::NorthWest,
+        ];
+
+        /// (row, column) step towards this side of the grid. North is row 0.
+        pub(crate) fn delta(self) -> (isize, isize) {
+            match self {
+                Direction::North => (-1, 0),
+                Direction::NorthEast => (-1, 1),
+                Direction::East => (0, 1),
+                Direction::SouthEast => (1, 1),
+                Direction::South => (1, 0),
+                Direction::SouthWest => (1, -1),
+                Direction::West => (0, -1),
+                Direction::NorthWest => (-1, -1),
+            }
+        }
+    }
+
+    /// Whether each tree can be seen from outside the grid looking in from `dir`, i.e. every tree
+    /// between it and that edge is strictly shorter.
+    pub(crate) fn visible_from(input: &[Vec<u8>], dir: Direction) -> Vec<Vec<bool>> {
+        let height = input.len();
+        let width = input.first().map_or(0, |row| row.len());
+        let (di, dj) = dir.delta();
+        // Tallest tree strictly between (i, j) and the edge; `None` on the edge itself.
+        let mut blocker: Vec<Vec<Option<u8>>> = vec![vec![None; width]; height];
+        let step = |i: usize, j: usize| -> Option<(usize, usize)> {
+            let i = i.checked_add_signed(di).filter(|&i| i < height)?;
+            let j = j.checked_add_signed(dj).filter(|&j| j < width)?;
+            Some((i, j))
+        };
+        // Visit cells so that the neighbour towards `dir` is always done first.
+        let rows = (0..height).collect_vec();
+        let cols = (0..width).collect_vec();
+        let rows: Box<dyn Iterator<Item = &usize>> = if di > 0 {
+            Box::new(rows.iter().rev())
+        } else {
+            Box::new(rows.iter())
+        };
+        for &i in rows {
+            let cols: Box<dyn Iterator<Item = &usize>> = if dj > 0 {
+                Box::new(cols.iter().rev())
+            } else {
+                Box::new(cols.iter())
+            };
+            for &j in cols {
+                blocker[i][j] = step(i, j).map(|(ni, nj)| match blocker[ni][nj] {
+                    Some(b) => b.max(input[ni][nj]),
+                    None => input[ni][nj],
+                });
+            }
+        }
+        input
+            .iter()
+            .zip(blocker)
+            .map(|(row, blocker)| {
+                row.iter()
+                    .zip(blocker)
+                    .map(|(&h, b)| b.is_none_or(|b| h > b))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The puzzle's scenic score for every tree, including edge trees (which always score 0).
+    #[cfg(test)]
+    pub(crate) fn scenic_scores(input: &[Vec<u8>]) -> Vec<Vec<usize>> {
+        (0..input.len())
+            .map(|i| {
+                (0..input[i].len())
+                    .map(|j| super::scenic_score_at(input, i, j))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// For each tree, how many trees it sees looking back towards the start of `heights`.
+    ///
+    /// Keeps a stack of indices with non-increasing heights: everything shorter than the current
+    /// tree is popped, as the current tree hides it from every later tree that it could block.
+    /// The remaining top is the first tree at least as tall, so each tree is pushed and popped
+    /// once.
+    fn viewing_distances(heights: impl Iterator<Item = u8>) -> Vec<usize> {
+        let mut stack: Vec<(usize, u8)> = vec![];
+        heights
+            .enumerate()
+            .map(|(j, h)| {
+                while stack.last().is_some_and(|&(_, top)| top < h) {
+                    stack.pop();
+                }
+                let distance = stack.last().map_or(j, |&(k, _)| j - k);
+                stack.push((j, h));
+                distance
+            })
+            .collect()
+    }
+
+    /// Same as [`scenic_scores`], in O(width * height) using a monotonic stack per line of sight.
+    pub(crate) fn scenic_scores_fast(input: &[Vec<u8>]) -> Vec<Vec<usize>> {
+        let height = input.len();
+        let width = input.first().map_or(0, |row| row.len());
+        let mut scores = vec![vec![1; width]; height];
+        for (row, out) in input.iter().zip(scores.iter_mut()) {
+            let left = viewing_distances(row.iter().copied());
+            let right = viewing_distances(row.iter().copied().rev());
+            for (j, score) in out.iter_mut().enumerate() {
+                *score *= left[j] * right[width - 1 - j];
+            }
+        }
+        for j in 0..width {
+            let up = viewing_distances(input.iter().map(|row| row[j]));
+            let down = viewing_distances(input.iter().rev().map(|row| row[j]));
+            for (i, row) in scores.iter_mut().enumerate() {
+                row[j] *= up[i] * down[height - 1 - i];
+            }
+        }
+        scores
+    }
+
+    /// The `k` highest-scoring trees as `((row, col), score)`, best first. Ties keep reading order.
+    pub(crate) fn top_k(scores: &[Vec<usize>], k: usize) -> Vec<((usize, usize), usize)> {
+        scores
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| row.iter().enumerate().map(move |(j, &s)| ((i, j), s)))
+            .sorted_by(|(_, a), (_, b)| b.cmp(a))
+            .take(k)
+            .collect()
+    }
+
+    /// Render a score map as a plain (P2) PGM image, scaled so the best score is white.
+    pub(crate) fn heatmap_pgm(
+        scores: &[Vec<usize>],
+        out: &mut impl std::io::Write,
+    ) -> std::io::Result<()> {
+        let height = scores.len();
+        let width = scores.first().map_or(0, |row| row.len());
+        let max = scores.iter().flatten().copied().max().unwrap_or(0).max(1);
+        writeln!(out, "P2\n{width} {height}\n255")?;
+        for row in scores {
+            writeln!(out, "{}", row.iter().map(|&s| s * 255 / max).join(" "))?;
+        }
+        Ok(())
+    }
+
+    /// Render a score map as text, one character per tree, from ` ` (lowest) to `@` (highest).
+    pub(crate) fn heatmap_ascii(scores: &[Vec<usize>]) -> String {
+        const RAMP: &[u8] = b" .:-=+*#%@";
+        let max = scores.iter().flatten().copied().max().unwrap_or(0).max(1);
+        scores
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&s| RAMP[s * (RAMP.len() - 1) / max] as char)
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
+
+    /// Cells visited by a Bresenham line from `from` to `to`, both ends included.
+    pub(crate) fn bresenham(from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+        let (mut i, mut j) = (from.0 as isize, from.1 as isize);
+        let (ti, tj) = (to.0 as isize, to.1 as isize);
+        let (di, dj) = ((ti - i).abs(), -(tj - j).abs());
+        let (si, sj) = ((ti - i).signum(), (tj - j).signum());
+        let mut err = di + dj;
+        let mut out = vec![(i as usize, j as usize)];
+        while (i, j) != (ti, tj) {
+            let e2 = 2 * err;
+            if e2 >= dj {
+                err += dj;
+                i += si;
+            }
+            if e2 <= di {
+                err += di;
+                j += sj;
+            }
+            out.push((i as usize, j as usize));
+        }
+        out
+    }
+
+    /// Whether the top of tree `a` can see the top of tree `b`.
+    ///
+    /// The sight line runs straight between the two tree tops along the Bresenham ray; a tree in
+    /// between blocks it if it reaches the line's height at that point.
+    pub(crate) fn line_of_sight(input: &[Vec<u8>], a: (usize, usize), b: (usize, usize)) -> bool {
+        let ray = bresenham(a, b);
+        let n = ray.len() as i64 - 1;
+        let (ha, hb) = (input[a.0][a.1] as i64, input[b.0][b.1] as i64);
+        ray.iter()
+            .enumerate()
+            .skip(1)
+            .take((n - 1).max(0) as usize)
+            // h >= ha + k / n * (hb - ha), scaled by n
+            .all(|(k, &(i, j))| (input[i][j] as i64) * n < ha * n + k as i64 * (hb - ha))
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::{read_input, to_visible};
+        use proptest::prelude::*;
+
+        const SAMPLE: &str = "30373\n25512\n65332\n33549\n35390\n";
+
+        fn sample() -> Vec<Vec<u8>> {
+            read_input(SAMPLE.as_bytes()).unwrap()
+        }
+
+        fn q2_naive(input: Vec<Vec<u8>>) -> usize {
+            let height = input.len();
+            let width = input[0].len();
+            (1..height - 1)
+                .cartesian_product(1..width - 1)
+                .map(|(i, j)| crate::scenic_score_at(&input, i, j))
+                .max()
+                .unwrap_or(0)
+        }
+
+        #[test]
+        fn test_cardinal_visibility_matches_to_visible() {
+            let input = sample();
+            let cardinal = [
+                Direction::North,
+                Direction::East,
+                Direction::South,
+                Direction::West,
+            ]
+            .map(|d| visible_from(&input, d));
+            let union = (0..5)
+                .map(|i| {
+                    (0..5)
+                        .map(|j| cardinal.iter().any(|v| v[i][j]))
+                        .collect_vec()
+                })
+                .collect_vec();
+            assert_eq!(union, to_visible(&input));
+        }
+
+        #[test]
+        fn test_diagonal_visibility() {
+            let input = sample();
+            let v = visible_from(&input, Direction::NorthWest);
+            // (1, 1) is 5, behind the 3 at (0, 0).
+            assert!(v[1][1]);
+            // (2, 2) is 3, behind the 5 at (1, 1).
+            assert!(!v[2][2]);
+            assert!(v[0].iter().all(|&x| x));
+            assert!(v.iter().all(|row| row[0]));
+        }
+
+        #[test]
+        fn test_scenic_scores() {
+            let scores = scenic_scores(&sample());
+            assert_eq!(scores[1][2], 4);
+            assert_eq!(scores[3][2], 8);
+            assert_eq!(top_k(&scores, 2), vec![((3, 2), 8), ((2, 1), 6)]);
+        }
+
+        #[test]
+        fn test_scenic_scores_fast() {
+            let input = sample();
+            assert_eq!(scenic_scores_fast(&input), scenic_scores(&input));
+            assert_eq!(crate::q2(input.clone()), q2_naive(input));
+        }
+
+        proptest! {
+            #[test]
+            fn fast_scenic_scores_agree_with_naive(
+                input in (1..20usize, 1..20usize).prop_flat_map(|(h, w)| {
+                    prop::collection::vec(prop::collection::vec(0..10u8, w), h)
+                })
+            ) {
+                prop_assert_eq!(scenic_scores_fast(&input), scenic_scores(&input));
+            }
+        }
+
+        #[test]
+        fn test_bresenham() {
+            assert_eq!(
+                bresenham((0, 0), (2, 4)),
+                vec![(0, 0), (1, 1), (1, 2), (2, 3), (2, 4)]
+            );
+            assert_eq!(bresenham((3, 1), (3, 1)), vec![(3, 1)]);
+            assert_eq!(bresenham((2, 2), (0, 2)), vec![(2, 2), (1, 2), (0, 2)]);
+        }
+
+        #[test]
+        fn test_line_of_sight() {
+            let input = sample();
+            // The 5s at (2, 1) and (2, 2) stand above the line from the 6 down to the 3.
+            assert!(!line_of_sight(&input, (2, 0), (2, 3)));
+            // Neighbours always see each other.
+            assert!(line_of_sight(&input, (0, 0), (0, 1)));
+            // 9 at (3, 4) over the 4 at (3, 3) to the 5 at (3, 2).
+            assert!(line_of_sight(&input, (3, 4), (3, 2)));
+        }
+    }
 }
@@ -2,16 +2,102 @@ use std::io::Read;
 
 use itertools::Itertools;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum Direction {
     Left,
     Right,
     Up,
     Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
-#[derive(Copy, Clone, Debug)]
-struct Command(Direction, u8);
+impl Direction {
+    fn delta(self) -> (i32, i32) {
+        match self {
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::UpLeft => (-1, 1),
+            Direction::UpRight => (1, 1),
+            Direction::DownLeft => (-1, -1),
+            Direction::DownRight => (1, -1),
+        }
+    }
+
+    fn from_delta(delta: (i32, i32)) -> Option<Self> {
+        Some(match (delta.0.signum(), delta.1.signum()) {
+            (-1, 0) => Direction::Left,
+            (1, 0) => Direction::Right,
+            (0, 1) => Direction::Up,
+            (0, -1) => Direction::Down,
+            (-1, 1) => Direction::UpLeft,
+            (1, 1) => Direction::UpRight,
+            (-1, -1) => Direction::DownLeft,
+            (1, -1) => Direction::DownRight,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Command(Direction, u32);
+
+/// Parsed input. Repeat blocks stay as they are written and are only expanded while iterating, so
+/// a large repeat count costs no memory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Block {
+    Command(Command),
+    Repeat(u32, Vec<Block>),
+}
+
+impl Block {
+    /// How many commands the block expands to, or `None` if that doesn't fit in a `u64`.
+    fn len(&self) -> Option<u64> {
+        match self {
+            Block::Command(_) => Some(1),
+            Block::Repeat(times, body) => body
+                .iter()
+                .try_fold(0u64, |acc, b| acc.checked_add(b.len()?))?
+                .checked_mul(u64::from(*times)),
+        }
+    }
+
+    fn commands(&self) -> Box<dyn Iterator<Item = Command> + '_> {
+        match self {
+            Block::Command(c) => Box::new(std::iter::once(*c)),
+            Block::Repeat(times, body) => {
+                Box::new((0..*times).flat_map(move |_| body.iter().flat_map(Block::commands)))
+            }
+        }
+    }
+}
+
+fn expand(blocks: &[Block]) -> impl Iterator<Item = Command> + '_ {
+    blocks.iter().flat_map(Block::commands)
+}
+
+/// Split an arbitrary `(dx, dy)` vector into unit-step commands: a diagonal run first, then a
+/// straight run for whatever is left on the longer axis.
+fn vector_commands(dx: i32, dy: i32) -> impl Iterator<Item = Command> {
+    let (x, y) = (dx.unsigned_abs(), dy.unsigned_abs());
+    let diagonal = x.min(y);
+    let straight = if x > y {
+        (dx.signum(), 0)
+    } else {
+        (0, dy.signum())
+    };
+    [
+        (Direction::from_delta((dx, dy)), diagonal),
+        (Direction::from_delta(straight), x.max(y) - diagonal),
+    ]
+    .into_iter()
+    .filter_map(|(dir, cnt)| Some(Command(dir?, cnt)))
+    .filter(|Command(_, cnt)| *cnt > 0)
+}
 
 mod parse {
     use super::*;
@@ -20,29 +106,218 @@ mod parse {
     use chumsky::error::Simple;
     use chumsky::text::newline;
 
-    fn command() -> impl Parser<char, Command, Error = Simple<char>> {
+    fn inline_whitespace() -> impl Parser<char, (), Error = Simple<char>> + Clone {
+        filter(|c: &char| c.is_whitespace() && *c != '\n')
+            .repeated()
+            .ignored()
+    }
+
+    fn count() -> impl Parser<char, u32, Error = Simple<char>> + Clone {
+        text::int(10).try_map(|s: String, span| {
+            s.parse::<u32>()
+                .map_err(|e| Simple::custom(span, format!("{e}")))
+        })
+    }
+
+    fn int() -> impl Parser<char, i32, Error = Simple<char>> + Clone {
+        // Parse the sign along with the digits, so that `i32::MIN` fits.
+        just('-')
+            .or_not()
+            .then(text::int(10))
+            .try_map(|(sign, s): (Option<char>, String), span| {
+                format!("{}{s}", sign.map_or("", |_| "-"))
+                    .parse::<i32>()
+                    .map_err(|e| Simple::custom(span, format!("{e}")))
+            })
+    }
+
+    fn direction() -> impl Parser<char, Direction, Error = Simple<char>> + Clone {
         choice((
-            just('L').to(Direction::Left),
-            just('R').to(Direction::Right),
-            just('U').to(Direction::Up),
-            just('D').to(Direction::Down),
+            just("UL").to(Direction::UpLeft),
+            just("UR").to(Direction::UpRight),
+            just("DL").to(Direction::DownLeft),
+            just("DR").to(Direction::DownRight),
+            just("L").to(Direction::Left),
+            just("R").to(Direction::Right),
+            just("U").to(Direction::Up),
+            just("D").to(Direction::Down),
         ))
-        .padded()
-        .then(text::int(10).try_map(|s: String, span| {
-            s.parse::<u8>()
-                .map_err(|e| Simple::custom(span, format!("{e}")))
-        }))
-        .map(|(dir, cnt)| Command(dir, cnt))
     }
 
-    pub(crate) fn commands() -> impl Parser<char, Vec<Command>, Error = Simple<char>> {
-        command()
+    /// `R 4`, `UL 2`
+    fn command() -> impl Parser<char, Vec<Block>, Error = Simple<char>> + Clone {
+        direction()
+            .then_ignore(inline_whitespace())
+            .then(count())
+            .map(|(dir, cnt)| vec![Block::Command(Command(dir, cnt))])
+    }
+
+    /// `3,-2`
+    fn vector() -> impl Parser<char, Vec<Block>, Error = Simple<char>> + Clone {
+        int()
+            .then_ignore(just(',').padded_by(inline_whitespace()))
+            .then(int())
+            .map(|(dx, dy)| vector_commands(dx, dy).map(Block::Command).collect())
+    }
+
+    /// A single command, a vector, or a repeat block like `3x(R 2, U 1)`. Repeat blocks nest.
+    fn item() -> impl Parser<char, Vec<Block>, Error = Simple<char>> + Clone {
+        recursive(|item| {
+            let repeat = count()
+                .then_ignore(just('x'))
+                .then_ignore(inline_whitespace())
+                .then(
+                    item.padded_by(inline_whitespace())
+                        .separated_by(just(','))
+                        .at_least(1)
+                        .delimited_by(just('('), just(')')),
+                )
+                .try_map(|(times, body): (u32, Vec<Vec<Block>>), span| {
+                    let block = Block::Repeat(times, body.concat());
+                    match block.len() {
+                        Some(_) => Ok(vec![block]),
+                        None => Err(Simple::custom(span, "repeat block is too long")),
+                    }
+                });
+            choice((repeat, vector(), command()))
+        })
+    }
+
+    /// One line of input. A bad line is reported and skipped, so that every bad line in the
+    /// input ends up in the error list instead of only the first one. Blank lines are empty.
+    fn line() -> impl Parser<char, Vec<Block>, Error = Simple<char>> + Clone {
+        let line_end = || newline().or(end()).rewind();
+        let blank = inline_whitespace().then(line_end()).to(Vec::new());
+        // Only try (and recover) a line when there is input left, so a trailing newline is fine.
+        any().rewind().ignore_then(
+            blank.or(item()
+                .padded_by(inline_whitespace())
+                .then_ignore(line_end())
+                .recover_with(skip_until(['\n'], |_| Vec::new()))),
+        )
+    }
+
+    pub(crate) fn commands() -> impl Parser<char, Vec<Block>, Error = Simple<char>> {
+        line()
             .separated_by(newline())
             .allow_trailing()
             .then_ignore(end())
+            .map(|lines| lines.concat())
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use Direction::*;
+
+        fn parse_flat(s: &str) -> Result<Vec<Command>, Vec<Simple<char>>> {
+            commands().parse(s).map(|blocks| expand(&blocks).collect())
+        }
+
+        #[test]
+        fn test_cardinal() {
+            assert_eq!(
+                parse_flat("R 4\nU 4\nL 3\n"),
+                Ok(vec![Command(Right, 4), Command(Up, 4), Command(Left, 3)])
+            );
+        }
+
+        #[test]
+        fn test_blank_lines() {
+            assert_eq!(
+                parse_flat("R 4\n\nU 4\n  \t\nL 3\n\n"),
+                Ok(vec![Command(Right, 4), Command(Up, 4), Command(Left, 3)])
+            );
+        }
+
+        #[test]
+        fn test_diagonal() {
+            assert_eq!(
+                parse_flat("UL 2\nDR 1"),
+                Ok(vec![Command(UpLeft, 2), Command(DownRight, 1)])
+            );
+        }
+
+        #[test]
+        fn test_vector() {
+            assert_eq!(
+                parse_flat("3,-5\n-2, 0\n0,0"),
+                Ok(vec![
+                    Command(DownRight, 3),
+                    Command(Down, 2),
+                    Command(Left, 2)
+                ])
+            );
+        }
+
+        #[test]
+        fn test_repeat() {
+            assert_eq!(
+                parse_flat("2x(R 2, U 1)"),
+                Ok(vec![
+                    Command(Right, 2),
+                    Command(Up, 1),
+                    Command(Right, 2),
+                    Command(Up, 1)
+                ])
+            );
+            assert_eq!(
+                parse_flat("2x(1,1, 2x(D 1))"),
+                Ok(vec![
+                    Command(UpRight, 1),
+                    Command(Down, 1),
+                    Command(Down, 1),
+                    Command(UpRight, 1),
+                    Command(Down, 1),
+                    Command(Down, 1),
+                ])
+            );
+        }
+
+        #[test]
+        fn test_large_repeat_is_lazy() {
+            let blocks = commands()
+                .parse("4000000000x(R 1, 4000000000x(U 1))")
+                .unwrap();
+            assert_eq!(blocks[0].len(), Some(4_000_000_000 * 4_000_000_001));
+            assert_eq!(
+                expand(&blocks).take(3).collect::<Vec<_>>(),
+                vec![Command(Right, 1), Command(Up, 1), Command(Up, 1)]
+            );
+            // 2^32 repeated three times overflows a `u64`.
+            let nested = "4294967295x(4294967295x(4294967295x(R 1, R 1)))";
+            assert!(commands().parse(nested).is_err());
+        }
+
+        #[test]
+        fn test_int_bounds() {
+            assert_eq!(
+                parse_flat("-2147483648,2147483647"),
+                Ok(vec![Command(UpLeft, 2147483647), Command(Left, 1)])
+            );
+            assert!(parse_flat("-2147483649,0").is_err());
+        }
+
+        #[test]
+        fn test_reports_every_bad_line() {
+            const INPUT: &str = "R 1\nX 2\nU 1\nR\nL 1 2\n";
+            let (out, errors) = commands().parse_recovery(INPUT);
+            let out = out.map(|blocks| expand(&blocks).collect::<Vec<_>>());
+            assert_eq!(out, Some(vec![Command(Right, 1), Command(Up, 1)]));
+            let bad_lines = errors
+                .iter()
+                .map(|e| line_number(INPUT, e.span().start))
+                .collect::<Vec<_>>();
+            assert_eq!(bad_lines, vec![2, 4, 5]);
+        }
     }
 }
 
+/// 1-based line number of the `pos`-th character of `input`.
+fn line_number(input: &str, pos: usize) -> usize {
+    input.chars().take(pos).filter(|&c| c == '\n').count() + 1
+}
+
 fn tail_pos(head_pos: (i32, i32), mut tail_pos: (i32, i32)) -> (i32, i32) {
     let delta = (
         head_pos.0 - tail_pos.0,
@@ -80,12 +355,9 @@ impl Rope {
         }
     }
     pub(crate) fn move_to(&mut self, dir: Direction) {
-        match dir {
-            Direction::Left => self.head_pos.0 -= 1,
-            Direction::Right => self.head_pos.0 += 1,
-            Direction::Up => self.head_pos.1 += 1,
-            Direction::Down => self.head_pos.1 -= 1,
-        }
+        let (dx, dy) = dir.delta();
+        self.head_pos.0 += dx;
+        self.head_pos.1 += dy;
         let mut prev_pos = self.head_pos;
         self.tail_pos.iter_mut()
             .for_each(|tail| {
@@ -96,17 +368,21 @@ impl Rope {
     }
 }
 
-use chumsky::error::Error;
 use chumsky::prelude::Parser;
 fn main() -> anyhow::Result<()> {
     let mut input = String::new();
     std::io::stdin().lock().read_to_string(&mut input)?;
-    let input = parse::commands().parse(input).map_err(|e| {
-        let error = e.into_iter().reduce(|lhs, rhs| lhs.merge(rhs)).unwrap();
-        anyhow::anyhow!("{error}")
-    })?;
-    println!("q1: {}", q1(input.iter().cloned()));
-    println!("q2: {}", q2(input.into_iter()));
+    let (commands, errors) = parse::commands().parse_recovery(input.as_str());
+    if !errors.is_empty() {
+        let report = errors
+            .iter()
+            .map(|e| format!("line {}: {e}", line_number(&input, e.span().start)))
+            .join("\n");
+        anyhow::bail!("{} bad line(s)\n{report}", errors.len());
+    }
+    let input = commands.expect("no errors were reported");
+    println!("q1: {}", q1(expand(&input)));
+    println!("q2: {}", q2(expand(&input)));
     Ok(())
 }
 
@@ -115,7 +391,7 @@ fn q1(input: impl Iterator<Item = Command>) -> usize {
     std::iter::once((0, 0))
         .chain(
             input
-                .flat_map(|Command(dir, repeat)| std::iter::repeat(dir).take(repeat as usize))
+                .flat_map(|Command(dir, repeat)| std::iter::repeat_n(dir, repeat as usize))
                 .map(|dir| {
                     rope.move_to(dir);
                     rope.tail_pos.last().copied().unwrap()
@@ -130,7 +406,7 @@ fn q2(input: impl Iterator<Item = Command>) -> usize {
     std::iter::once((0, 0))
         .chain(
             input
-                .flat_map(|Command(dir, repeat)| std::iter::repeat(dir).take(repeat as usize))
+                .flat_map(|Command(dir, repeat)| std::iter::repeat_n(dir, repeat as usize))
                 .map(|dir| {
                     rope.move_to(dir);
                     rope.tail_pos.last().copied().unwrap()
@@ -76,7 +76,7 @@ impl TryFrom<&'_ str> for MapState {
                     })
                     .collect::<Result<Vec<u8>, ParseError>>()?;
                 if out.len() != self.col_size {
-                    return Err(ParseError::BadRow { row_no });
+                    return Err(ParseError::InconsistentRowSize { row_no });
                 }
                 self.row_cnt += 1;
                 Ok(out)
@@ -114,7 +114,185 @@ struct BFSOutput {
     steps: usize,
 }
 
+/// Steps from a BFS source to every cell, `None` where the source can't reach.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DistanceField(Vec<Vec<Option<usize>>>);
+
+impl DistanceField {
+    fn get(&self, (x, y): Position) -> Option<usize> {
+        self.0[x][y]
+    }
+}
+
+/// One row per line, with `.` for unreachable cells.
+impl std::fmt::Display for DistanceField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .0
+            .iter()
+            .flatten()
+            .flatten()
+            .max()
+            .map_or(1, |d| d.to_string().len());
+        for row in &self.0 {
+            let cells = row
+                .iter()
+                .map(|d| match d {
+                    Some(d) => format!("{d:>width$}"),
+                    None => format!("{:>width$}", "."),
+                })
+                .collect::<Vec<_>>();
+            writeln!(f, "{}", cells.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Extra cost of a step, on top of `base`, per unit of height gained or lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StepCost {
+    base: u64,
+    per_ascent: u64,
+    per_descent: u64,
+}
+
+/// What a hiker can climb, and what it costs them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ClimbRules {
+    max_ascent: Height,
+    /// `None` for no limit.
+    max_descent: Option<Height>,
+    diagonal: bool,
+    cost: StepCost,
+}
+
+impl Default for ClimbRules {
+    /// The puzzle's rule: step at most one up, and down as far as you like, one step at a time.
+    fn default() -> Self {
+        Self {
+            max_ascent: 1,
+            max_descent: None,
+            diagonal: false,
+            cost: StepCost {
+                base: 1,
+                per_ascent: 0,
+                per_descent: 0,
+            },
+        }
+    }
+}
+
+impl ClimbRules {
+    fn allows(&self, from: Height, to: Height) -> bool {
+        if to >= from {
+            to - from <= self.max_ascent
+        } else {
+            self.max_descent.is_none_or(|max| from - to <= max)
+        }
+    }
+
+    fn cost(&self, from: Height, to: Height) -> u64 {
+        let StepCost {
+            base,
+            per_ascent,
+            per_descent,
+        } = self.cost;
+        base + per_ascent * u64::from(to.saturating_sub(from))
+            + per_descent * u64::from(from.saturating_sub(to))
+    }
+}
+
+#[derive(Debug)]
+struct DijkstraOutput {
+    dest: Position,
+    cost: u64,
+}
+
 impl MapState {
+    fn neighbors(&self, pos: Position) -> impl Iterator<Item = Position> + '_ {
+        self.neighbors_with(pos, false)
+    }
+
+    fn neighbors_with(
+        &self,
+        (x, y): Position,
+        diagonal: bool,
+    ) -> impl Iterator<Item = Position> + '_ {
+        const ORTHOGONAL: [(isize, isize); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
+        const DIAGONAL: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+        let diagonals = if diagonal { &DIAGONAL[..] } else { &[] };
+        ORTHOGONAL
+            .iter()
+            .chain(diagonals)
+            .filter_map(move |&(dx, dy)| {
+                let x = x.checked_add_signed(dx).filter(|&x| x < self.max_rows)?;
+                let y = y.checked_add_signed(dy).filter(|&y| y < self.max_cols)?;
+                Some((x, y))
+            })
+    }
+
+    fn height(&self, (x, y): Position) -> Height {
+        self.map[x][y]
+    }
+
+    /// The puzzle's rule: step at most one up, and down as far as you like.
+    fn can_climb(&self, from: Position, to: Position) -> bool {
+        ClimbRules::default().allows(self.height(from), self.height(to))
+    }
+
+    /// Cheapest route under `rules` from `start` to a cell that satisfies `is_end`. With
+    /// `reverse`, searches backwards: the hiker walks from the end cell to `start`.
+    fn dijkstra<F>(
+        &self,
+        start: Position,
+        rules: &ClimbRules,
+        reverse: bool,
+        mut is_end: F,
+    ) -> Option<DijkstraOutput>
+    where
+        F: FnMut(Position) -> bool,
+    {
+        use std::cmp::Reverse;
+        use std::collections::BinaryHeap;
+
+        let mut cost = vec![vec![u64::MAX; self.max_cols]; self.max_rows];
+        cost[start.0][start.1] = 0;
+        let mut queue = BinaryHeap::from([Reverse((0, start))]);
+        while let Some(Reverse((c, pos))) = queue.pop() {
+            if c > cost[pos.0][pos.1] {
+                continue;
+            }
+            if is_end(pos) {
+                return Some(DijkstraOutput { dest: pos, cost: c });
+            }
+            for next in self.neighbors_with(pos, rules.diagonal) {
+                let (from, to) = if reverse { (next, pos) } else { (pos, next) };
+                let (from, to) = (self.height(from), self.height(to));
+                if !rules.allows(from, to) {
+                    continue;
+                }
+                let c2 = c + rules.cost(from, to);
+                if c2 < cost[next.0][next.1] {
+                    cost[next.0][next.1] = c2;
+                    queue.push(Reverse((c2, next)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Cheapest climb from the start to the target under `rules`.
+    fn climb_with(&self, rules: &ClimbRules) -> Option<u64> {
+        self.dijkstra(self.start, rules, false, |pos| pos == self.target)
+            .map(|out| out.cost)
+    }
+
+    /// Cheapest climb from any lowest cell to the target under `rules`.
+    fn min_cost_from_lowest(&self, rules: &ClimbRules) -> Option<u64> {
+        self.dijkstra(self.target, rules, true, |pos| self.height(pos) == 0)
+            .map(|out| out.cost)
+    }
+
     fn bfs<E, F>(
         &self,
         start: (usize, usize),
@@ -133,23 +311,14 @@ impl MapState {
             steps += 1;
             let mut new_pos = vec![];
             for (x, y) in pos {
-                let h = self.map[x][y];
-                let neighbors = [
-                    x.checked_sub(1).map(|x| (x, y)),
-                    y.checked_sub(1).map(|y| (x, y)),
-                    ((x + 1) < self.max_rows).then_some((x + 1, y)),
-                    ((y + 1) < self.max_cols).then_some((x, y + 1)),
-                ];
-                for (x2, y2) in neighbors
-                    .into_iter()
-                    .filter_map(|i| i)
+                for (x2, y2) in self
+                    .neighbors((x, y))
                     .filter(|&(x2, y2)| is_connected((x, y), (x2, y2)))
                 {
                     if visited[x2][y2] {
                         continue;
                     }
                     visited[x2][y2] = true;
-                    let h2 = self.map[x2][y2];
                     if is_end((x2, y2)) {
                         return Some(BFSOutput {
                             steps,
@@ -164,10 +333,98 @@ impl MapState {
         None
     }
 
+    /// Like `bfs`, but keeps going until every reachable cell has its distance.
+    fn distance_field<E>(&self, start: Position, mut is_connected: E) -> DistanceField
+    where
+        E: FnMut(Position, Position) -> bool,
+    {
+        let mut dist = vec![vec![None; self.max_cols]; self.max_rows];
+        dist[start.0][start.1] = Some(0);
+        let mut pos = vec![start];
+        let mut steps = 0;
+        while !pos.is_empty() {
+            steps += 1;
+            let mut new_pos = vec![];
+            for p in pos {
+                for (x2, y2) in self.neighbors(p).filter(|&p2| is_connected(p, p2)) {
+                    if dist[x2][y2].is_none() {
+                        dist[x2][y2] = Some(steps);
+                        new_pos.push((x2, y2));
+                    }
+                }
+            }
+            pos = new_pos;
+        }
+        DistanceField(dist)
+    }
+
+    fn distances_from_start(&self) -> DistanceField {
+        self.distance_field(self.start, |p1, p2| self.can_climb(p1, p2))
+    }
+
+    /// For every cell, the steps needed to climb from it to the target.
+    fn distances_to_target(&self) -> DistanceField {
+        self.distance_field(self.target, |p1, p2| self.can_climb(p2, p1))
+    }
+
+    /// A shortest path from the start to the target, both included.
+    fn shortest_path(&self) -> Option<Vec<Position>> {
+        let dist = self.distances_from_start();
+        let mut pos = self.target;
+        let mut path = vec![pos];
+        let mut d = dist.get(pos)?;
+        // Walk back down the distance field.
+        while d > 0 {
+            pos = self
+                .neighbors(pos)
+                .find(|&prev| dist.get(prev) == Some(d - 1) && self.can_climb(prev, pos))
+                .expect("every reached cell but the start has a predecessor");
+            path.push(pos);
+            d -= 1;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Draw the heightmap with `path` as arrows, as in the puzzle statement. Cells that can't be
+    /// reached from the start are drawn as `#`.
+    fn render_path(&self, path: &[Position]) -> String {
+        let reachable = self.distances_from_start();
+        let mut grid = self
+            .map
+            .iter()
+            .zip(0..)
+            .map(|(row, x)| {
+                row.iter()
+                    .zip(0..)
+                    .map(|(&h, y)| match reachable.get((x, y)) {
+                        Some(_) => (b'a' + h) as char,
+                        None => '#',
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        grid[self.start.0][self.start.1] = 'S';
+        grid[self.target.0][self.target.1] = 'E';
+        for step in path.windows(2) {
+            let ((x1, y1), (x2, y2)) = (step[0], step[1]);
+            grid[x1][y1] = match (x2 as isize - x1 as isize, y2 as isize - y1 as isize) {
+                (-1, 0) => '^',
+                (1, 0) => 'v',
+                (0, -1) => '<',
+                (0, 1) => '>',
+                _ => panic!("path steps from {:?} to {:?}", step[0], step[1]),
+            };
+        }
+        grid.iter()
+            .map(|row| row.iter().collect::<String>() + "\n")
+            .collect()
+    }
+
     fn climb(&self) -> usize {
         self.bfs(
             self.start,
-            |(x1, y1), (x2, y2)| self.map[x1][y1] + 1 >= self.map[x2][y2],
+            |p1, p2| self.can_climb(p1, p2),
             |pos| pos == self.target,
         )
         .expect("BFS cannot reach the target")
@@ -176,7 +433,7 @@ impl MapState {
     fn min_steps_to_lowest(&self) -> usize {
         self.bfs(
             self.target,
-            |(x1, y1), (x2, y2)| self.map[x2][y2] + 1 >= self.map[x1][y1],
+            |p1, p2| self.can_climb(p2, p1),
             |(x, y)| self.map[x][y] == 0,
         )
         .expect("BFS cannot reach the lowest point")
@@ -184,10 +441,293 @@ impl MapState {
     }
 }
 
+mod generator {
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+    use std::collections::VecDeque;
+
+    use super::Position;
+
+    #[derive(thiserror::Error, Debug, PartialEq, Eq)]
+    pub(crate) enum GenerateError {
+        #[error("A {rows}x{cols} map has no path of the 25 steps needed to climb from a to z")]
+        TooSmall { rows: usize, cols: usize },
+    }
+
+    /// Generates maze-like height maps that can always be climbed under the puzzle's rule.
+    ///
+    /// Corridors form a tree, carved between cells at even coordinates, and walls are `a` so that
+    /// nothing can be climbed through them. Heights rise evenly along the corridor from `S` to
+    /// `E`; the side branches are dead ends of similar height.
+    #[derive(Debug, Clone, Copy)]
+    pub(crate) struct Generator {
+        pub(crate) rows: usize,
+        pub(crate) cols: usize,
+        /// Aim for a path from `S` to `E` of about this many steps, or as long as the maze allows.
+        pub(crate) path_len: usize,
+        /// At most this many dead ends, or as many as the maze has.
+        pub(crate) dead_ends: Option<usize>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub(crate) struct GeneratedMap {
+        pub(crate) text: String,
+        /// Steps along the corridor from `S` to `E`. Walls can shortcut the first few steps.
+        pub(crate) path_len: usize,
+        pub(crate) dead_ends: usize,
+    }
+
+    const MIN_PATH_LEN: usize = 25;
+
+    impl Generator {
+        pub(crate) fn new(rows: usize, cols: usize) -> Self {
+            Self {
+                rows,
+                cols,
+                path_len: usize::MAX,
+                dead_ends: None,
+            }
+        }
+
+        fn open_neighbors<'a>(
+            &self,
+            open: &'a [Vec<bool>],
+            (x, y): Position,
+        ) -> impl Iterator<Item = Position> + 'a {
+            let (rows, cols) = (self.rows, self.cols);
+            [(-1, 0), (0, -1), (1, 0), (0, 1)]
+                .into_iter()
+                .filter_map(move |(dx, dy)| {
+                    let x = x.checked_add_signed(dx).filter(|&x| x < rows)?;
+                    let y = y.checked_add_signed(dy).filter(|&y| y < cols)?;
+                    open[x][y].then_some((x, y))
+                })
+        }
+
+        /// Randomized depth-first search over the cells at even coordinates.
+        fn carve(&self, rng: &mut StdRng) -> Vec<Vec<bool>> {
+            let mut open = vec![vec![false; self.cols]; self.rows];
+            let start = (
+                2 * rng.gen_range(0..self.rows.div_ceil(2)),
+                2 * rng.gen_range(0..self.cols.div_ceil(2)),
+            );
+            open[start.0][start.1] = true;
+            let mut stack = vec![start];
+            while let Some(&(x, y)) = stack.last() {
+                let mut next = [(-2, 0), (0, -2), (2, 0), (0, 2)]
+                    .into_iter()
+                    .filter_map(|(dx, dy)| {
+                        let x2 = x.checked_add_signed(dx).filter(|&x| x < self.rows)?;
+                        let y2 = y.checked_add_signed(dy).filter(|&y| y < self.cols)?;
+                        (!open[x2][y2]).then_some((x2, y2))
+                    })
+                    .collect::<Vec<_>>();
+                next.shuffle(rng);
+                match next.first() {
+                    Some(&(x2, y2)) => {
+                        open[(x + x2) / 2][(y + y2) / 2] = true;
+                        open[x2][y2] = true;
+                        stack.push((x2, y2));
+                    }
+                    None => {
+                        stack.pop();
+                    }
+                }
+            }
+            open
+        }
+
+        /// Distances and BFS parents of every open cell from `from`.
+        fn bfs(&self, open: &[Vec<bool>], from: Position) -> Vec<Vec<Option<(usize, Position)>>> {
+            let mut seen = vec![vec![None; self.cols]; self.rows];
+            seen[from.0][from.1] = Some((0, from));
+            let mut queue = VecDeque::from([from]);
+            while let Some(pos) = queue.pop_front() {
+                let (d, _) = seen[pos.0][pos.1].unwrap();
+                for (x, y) in self.open_neighbors(open, pos) {
+                    if seen[x][y].is_none() {
+                        seen[x][y] = Some((d + 1, pos));
+                        queue.push_back((x, y));
+                    }
+                }
+            }
+            seen
+        }
+
+        fn farthest(seen: &[Vec<Option<(usize, Position)>>]) -> (usize, Position) {
+            let mut best = (0, (0, 0));
+            for (x, row) in seen.iter().enumerate() {
+                for (y, cell) in row.iter().enumerate() {
+                    if let Some((d, _)) = cell {
+                        best = best.max((*d, (x, y)));
+                    }
+                }
+            }
+            best
+        }
+
+        pub(crate) fn generate(&self, seed: u64) -> Result<GeneratedMap, GenerateError> {
+            let too_small = GenerateError::TooSmall {
+                rows: self.rows,
+                cols: self.cols,
+            };
+            if self.rows == 0 || self.cols == 0 {
+                return Err(too_small);
+            }
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut open = self.carve(&mut rng);
+
+            // Start at one end of the longest corridor, so that any length it allows is possible.
+            let (_, start) = Self::farthest(&self.bfs(&open, (0, 0)));
+            let seen = self.bfs(&open, start);
+            let (longest, _) = Self::farthest(&seen);
+            if longest < MIN_PATH_LEN {
+                return Err(too_small);
+            }
+            let want = self.path_len.clamp(MIN_PATH_LEN, longest);
+            let (path_len, target) = seen
+                .iter()
+                .enumerate()
+                .flat_map(|(x, row)| row.iter().enumerate().map(move |(y, c)| (c, (x, y))))
+                .filter_map(|(c, pos)| c.map(|(d, _)| (d, pos)))
+                .filter(|&(d, _)| d >= want)
+                .min()
+                .expect("the longest path is at least as long");
+
+            let mut height = vec![vec![None; self.cols]; self.rows];
+            let mut pos = target;
+            for i in (0..=path_len).rev() {
+                height[pos.0][pos.1] = Some((25 * i / path_len) as u8);
+                pos = seen[pos.0][pos.1].unwrap().1;
+            }
+
+            // Close dead ends off the path until few enough are left.
+            let is_dead_end = |open: &[Vec<bool>], height: &[Vec<Option<u8>>], (x, y): Position| {
+                open[x][y]
+                    && height[x][y].is_none()
+                    && self.open_neighbors(open, (x, y)).count() == 1
+            };
+            let mut dead_ends = (0..self.rows)
+                .flat_map(|x| (0..self.cols).map(move |y| (x, y)))
+                .filter(|&pos| is_dead_end(&open, &height, pos))
+                .collect::<Vec<_>>();
+            let max_dead_ends = self.dead_ends.unwrap_or(usize::MAX);
+            while dead_ends.len() > max_dead_ends {
+                let (x, y) = dead_ends.swap_remove(rng.gen_range(0..dead_ends.len()));
+                open[x][y] = false;
+                let parent = self.open_neighbors(&open, (x, y)).next().unwrap();
+                if is_dead_end(&open, &height, parent) {
+                    dead_ends.push(parent);
+                }
+            }
+
+            // Branches wander up and down a little from where they leave the path.
+            let mut queue = (0..self.rows)
+                .flat_map(|x| (0..self.cols).map(move |y| (x, y)))
+                .filter(|&(x, y)| height[x][y].is_some())
+                .collect::<VecDeque<_>>();
+            while let Some(pos) = queue.pop_front() {
+                let h = height[pos.0][pos.1].unwrap();
+                for (x, y) in self.open_neighbors(&open, pos) {
+                    if height[x][y].is_none() {
+                        let h2 = (h as i32 + rng.gen_range(-2..=1)).clamp(0, 25);
+                        height[x][y] = Some(h2 as u8);
+                        queue.push_back((x, y));
+                    }
+                }
+            }
+
+            let mut grid = height
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|h| (b'a' + h.unwrap_or(0)) as char)
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            grid[start.0][start.1] = 'S';
+            grid[target.0][target.1] = 'E';
+            let text = grid
+                .iter()
+                .map(|row| row.iter().collect::<String>() + "\n")
+                .collect();
+            Ok(GeneratedMap {
+                text,
+                path_len,
+                dead_ends: dead_ends.len(),
+            })
+        }
+    }
+}
+
 fn main() -> anyhow::Result<()> {
+    const USAGE: &str = "usage: aoc_22_12 [--path] [--distances start|target] \
+        [--max-ascent N] [--max-descent N] [--diagonal] [--step-cost N] [--ascent-cost N] \
+        [--descent-cost N] < input\n       aoc_22_12 --generate ROWSxCOLS [--path-len N] \
+        [--dead-ends N] [--seed N]";
+    let mut show_path = false;
+    let mut distances = None;
+    let mut generate = None;
+    let mut seed = 0;
+    let mut rules = ClimbRules::default();
+    let mut args = std::env::args().skip(1);
+    let mut number = |arg: Option<String>| -> anyhow::Result<u64> {
+        Ok(arg.ok_or_else(|| anyhow::anyhow!(USAGE))?.parse()?)
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--path" => show_path = true,
+            "--distances" => distances = Some(args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?),
+            "--max-ascent" => rules.max_ascent = number(args.next())?.try_into()?,
+            "--max-descent" => rules.max_descent = Some(number(args.next())?.try_into()?),
+            "--diagonal" => rules.diagonal = true,
+            "--generate" => {
+                let size = args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                let (rows, cols) = size.split_once('x').ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                generate = Some(generator::Generator::new(rows.parse()?, cols.parse()?));
+            }
+            "--seed" => seed = number(args.next())?,
+            "--path-len" => {
+                let gen = generate.as_mut().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                gen.path_len = number(args.next())?.try_into()?;
+            }
+            "--dead-ends" => {
+                let gen = generate.as_mut().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                gen.dead_ends = Some(number(args.next())?.try_into()?);
+            }
+            "--step-cost" => rules.cost.base = number(args.next())?,
+            "--ascent-cost" => rules.cost.per_ascent = number(args.next())?,
+            "--descent-cost" => rules.cost.per_descent = number(args.next())?,
+            _ => anyhow::bail!(USAGE),
+        }
+    }
+    if let Some(generator) = generate {
+        print!("{}", generator.generate(seed)?.text);
+        return Ok(());
+    }
     let input = std::io::read_to_string(std::io::stdin())?;
     let state = MapState::try_from(input.as_str())?;
+    if show_path {
+        let path = state.shortest_path().unwrap_or_default();
+        println!("{}", state.render_path(&path));
+    }
+    match distances.as_deref() {
+        None => (),
+        Some("start") => println!("{}", state.distances_from_start()),
+        Some("target") => println!("{}", state.distances_to_target()),
+        Some(_) => anyhow::bail!(USAGE),
+    }
     println!("q1: {}", state.climb());
     println!("q2: {}", state.min_steps_to_lowest());
+    if rules != ClimbRules::default() {
+        let show = |cost: Option<u64>| cost.map_or("unreachable".to_string(), |c| c.to_string());
+        println!("q1 with custom rules: {}", show(state.climb_with(&rules)));
+        println!(
+            "q2 with custom rules: {}",
+            show(state.min_cost_from_lowest(&rules))
+        );
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,230 @@
+use super::*;
+
+const SAMPLE: &str = include_str!("../sample_input.txt");
+
+fn sample() -> MapState {
+    MapState::try_from(SAMPLE).unwrap()
+}
+
+#[test]
+fn test_sample() {
+    let state = sample();
+    assert_eq!(state.climb(), 31);
+    assert_eq!(state.min_steps_to_lowest(), 29);
+}
+
+#[test]
+fn test_distance_fields() {
+    let state = sample();
+    let from_start = state.distances_from_start();
+    assert_eq!(from_start.get(state.start), Some(0));
+    assert_eq!(from_start.get(state.target), Some(31));
+    let to_target = state.distances_to_target();
+    assert_eq!(to_target.get(state.target), Some(0));
+    assert_eq!(to_target.get(state.start), Some(31));
+    let lowest = (0..state.max_rows)
+        .flat_map(|x| (0..state.max_cols).map(move |y| (x, y)))
+        .filter(|&(x, y)| state.map[x][y] == 0)
+        .filter_map(|p| to_target.get(p))
+        .min();
+    assert_eq!(lowest, Some(29));
+}
+
+#[test]
+fn test_shortest_path() {
+    let state = sample();
+    let path = state.shortest_path().unwrap();
+    assert_eq!(path.len(), 32);
+    assert_eq!(path[0], state.start);
+    assert_eq!(path[31], state.target);
+    for step in path.windows(2) {
+        assert!(state.neighbors(step[0]).any(|p| p == step[1]));
+        assert!(state.can_climb(step[0], step[1]));
+    }
+    let rendered = state.render_path(&path);
+    assert_eq!(rendered.matches(['^', 'v', '<', '>']).count(), 31);
+    assert_eq!(rendered.lines().nth(2).unwrap().chars().nth(5), Some('E'));
+}
+
+#[test]
+fn test_render_unreachable() {
+    let state = MapState::try_from("Sbz\nabE\nzzz").unwrap();
+    assert_eq!(state.shortest_path(), None);
+    assert_eq!(state.render_path(&[]), "Sb#\nabE\n###\n");
+    let state = MapState::try_from("SbcdefghijklmnopqrstuvwxyE").unwrap();
+    let path = state.shortest_path().unwrap();
+    assert_eq!(state.render_path(&path), format!("{}E\n", ">".repeat(25)));
+    let to_target = state.distances_to_target().to_string();
+    assert!(to_target.starts_with("25 24 23 "));
+    assert!(to_target.ends_with(" 2  1  0\n"));
+    let state = MapState::try_from("Sbz\nabE\nbcd").unwrap();
+    assert_eq!(
+        state.distances_from_start().to_string(),
+        "0 1 .\n1 2 .\n2 3 4\n"
+    );
+}
+
+#[test]
+fn test_dijkstra_default_rules_match_bfs() {
+    for input in [SAMPLE, include_str!("../input.txt")] {
+        let state = MapState::try_from(input).unwrap();
+        let rules = ClimbRules::default();
+        assert_eq!(state.climb_with(&rules), Some(state.climb() as u64));
+        assert_eq!(
+            state.min_cost_from_lowest(&rules),
+            Some(state.min_steps_to_lowest() as u64)
+        );
+    }
+}
+
+#[test]
+fn test_climb_rules() {
+    let rules = ClimbRules {
+        max_ascent: 2,
+        max_descent: Some(1),
+        ..ClimbRules::default()
+    };
+    assert!(rules.allows(3, 5));
+    assert!(!rules.allows(3, 6));
+    assert!(rules.allows(3, 2));
+    assert!(!rules.allows(3, 1));
+    let rules = ClimbRules {
+        cost: StepCost {
+            base: 2,
+            per_ascent: 3,
+            per_descent: 5,
+        },
+        ..rules
+    };
+    assert_eq!(rules.cost(3, 3), 2);
+    assert_eq!(rules.cost(3, 5), 8);
+    assert_eq!(rules.cost(3, 2), 7);
+}
+
+#[test]
+fn test_custom_rules() {
+    // A ramp up to the target's height, with the target right below its top.
+    let state = MapState::try_from(
+        "Sbcdefghijklmnopqrstuvwxyz\n\
+         zzzzzzzzzzzzzzzzzzzzzzzzzE",
+    )
+    .unwrap();
+    let default = ClimbRules::default();
+    assert_eq!(state.climb_with(&default), Some(26));
+    let diagonal = ClimbRules {
+        diagonal: true,
+        ..default
+    };
+    assert_eq!(state.climb_with(&diagonal), Some(25));
+    let cautious = ClimbRules {
+        max_descent: Some(0),
+        ..default
+    };
+    assert_eq!(state.climb_with(&cautious), Some(26));
+    assert_eq!(state.min_cost_from_lowest(&cautious), Some(26));
+    let gear_limited = ClimbRules {
+        max_ascent: 0,
+        ..default
+    };
+    assert_eq!(state.climb_with(&gear_limited), None);
+    let steep = ClimbRules {
+        cost: StepCost {
+            base: 1,
+            per_ascent: 10,
+            per_descent: 0,
+        },
+        ..default
+    };
+    assert_eq!(state.climb_with(&steep), Some(26 + 250));
+}
+
+fn generated(rows: usize, cols: usize, seed: u64) -> String {
+    generator::Generator::new(rows, cols)
+        .generate(seed)
+        .unwrap()
+        .text
+}
+
+#[test]
+fn test_generated_maps_are_solvable() {
+    for seed in 0..20 {
+        for (rows, cols) in [(1, 60), (7, 9), (21, 40), (40, 8)] {
+            let map = generator::Generator::new(rows, cols)
+                .generate(seed)
+                .unwrap();
+            let state = MapState::try_from(map.text.as_str()).unwrap();
+            assert_eq!((state.max_rows, state.max_cols), (rows, cols));
+            let steps = state.climb();
+            assert!((25..=map.path_len).contains(&steps), "{}", map.text);
+            assert!(state.min_steps_to_lowest() <= steps);
+        }
+    }
+}
+
+#[test]
+fn test_generator_tuning() {
+    let mut gen = generator::Generator::new(31, 31);
+    assert_eq!(generated(31, 31, 7), gen.generate(7).unwrap().text);
+    gen.path_len = 40;
+    let map = gen.generate(7).unwrap();
+    assert_eq!(map.path_len, 40);
+    gen.dead_ends = Some(3);
+    let map = gen.generate(7).unwrap();
+    assert!(map.dead_ends <= 3);
+    // With few dead ends, most cells are walls.
+    let open = map
+        .text
+        .bytes()
+        .filter(|&b| b != b'a' && b != b'\n')
+        .count();
+    assert!(open < 200, "{}", map.text);
+    assert_eq!(
+        generator::Generator::new(4, 4).generate(0).unwrap_err(),
+        generator::GenerateError::TooSmall { rows: 4, cols: 4 }
+    );
+}
+
+#[test]
+fn test_large_generated_map() {
+    let text = generated(201, 201, 2022);
+    let state = MapState::try_from(text.as_str()).unwrap();
+    assert!(state.climb() >= 25);
+}
+
+#[test]
+fn test_parse_errors() {
+    let text = generated(9, 9, 1);
+    let parse = |s: &str| MapState::try_from(s).unwrap_err();
+    assert!(matches!(parse(""), ParseError::BadRow { row_no: 0 }));
+    let bad_char = text.replacen('a', "A", 1);
+    let row_no = text[..text.find('a').unwrap()].matches('\n').count();
+    assert!(matches!(parse(&bad_char), ParseError::BadRow { row_no: r } if r == row_no));
+    let mut rows = text.lines().map(str::to_string).collect::<Vec<_>>();
+    rows[4].push('a');
+    assert!(matches!(
+        parse(&rows.join("\n")),
+        ParseError::InconsistentRowSize { row_no: 4 }
+    ));
+    rows[4].pop();
+    let with_row = |row: &str| {
+        let mut rows = rows.clone();
+        rows.push(row.to_string());
+        rows.join("\n")
+    };
+    assert!(matches!(
+        parse(&with_row("Saaaaaaaa")),
+        ParseError::DuplicatedStart
+    ));
+    assert!(matches!(
+        parse(&with_row("aaaaaaaEa")),
+        ParseError::DuplicatedTarget
+    ));
+    assert!(matches!(
+        parse(&text.replace('S', "a")),
+        ParseError::NoStart
+    ));
+    assert!(matches!(
+        parse(&text.replace('E', "z")),
+        ParseError::NoTarget
+    ));
+}
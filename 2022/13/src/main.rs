@@ -1,6 +1,8 @@
-#[derive(Clone, Eq, PartialEq, Debug)]
+/// A packet. Note that `Eq` is structural while `Ord` is the puzzle's ordering, under which e.g.
+/// `2` and `[2]` are equal; compare `normalize`d signals, or use `Canonical`, to get both to agree.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 enum Signal {
-    Integer(i32),
+    Integer(u64),
     List(Vec<Signal>),
 }
 
@@ -19,17 +21,436 @@ impl Ord for Signal {
 
 impl PartialOrd for Signal {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(&other))
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Signal::Integer(i) => write!(f, "{i}"),
+            Signal::List(v) => {
+                write!(f, "[")?;
+                for (i, s) in v.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{s}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl From<u64> for Signal {
+    fn from(i: u64) -> Self {
+        Signal::Integer(i)
+    }
+}
+
+impl Signal {
+    /// Start building a list, e.g. `Signal::builder().int(1).list(|l| l.int(2)).build()` for
+    /// `[1,[2]]`.
+    fn builder() -> ListBuilder {
+        ListBuilder::default()
+    }
+
+    /// The simplest signal that compares equal to this one: a list holding a single integer, at
+    /// any depth, compares like the integer itself.
+    fn normalize(&self) -> Signal {
+        match self {
+            Signal::Integer(i) => Signal::Integer(*i),
+            Signal::List(v) => match v.as_slice() {
+                [s] => match s.normalize() {
+                    Signal::Integer(i) => Signal::Integer(i),
+                    s => Signal::List(vec![s]),
+                },
+                v => Signal::List(v.iter().map(Signal::normalize).collect()),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct ListBuilder(Vec<Signal>);
+
+impl ListBuilder {
+    fn int(self, i: u64) -> Self {
+        self.push(Signal::Integer(i))
+    }
+
+    fn list(self, f: impl FnOnce(ListBuilder) -> ListBuilder) -> Self {
+        self.push(f(ListBuilder::default()).build())
+    }
+
+    fn push(mut self, s: impl Into<Signal>) -> Self {
+        self.0.push(s.into());
+        self
+    }
+
+    fn build(self) -> Signal {
+        Signal::List(self.0)
+    }
+}
+
+/// A signal whose `Eq` and `Hash` agree with the puzzle's ordering, so that packets which compare
+/// equal can be deduplicated or used as one map key.
+#[derive(Clone, Eq, PartialEq, Hash, PartialOrd, Ord, Debug)]
+struct Canonical(Signal);
+
+impl From<Signal> for Canonical {
+    fn from(s: Signal) -> Self {
+        Canonical(s.normalize())
+    }
+}
+
+mod trace {
+    use super::Signal;
+    use std::cmp::Ordering;
+
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub(crate) enum Side {
+        Left,
+        Right,
+    }
+
+    /// What settled the comparison.
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub(crate) enum Reason {
+        /// This side's integer was smaller.
+        Smaller(Side),
+        /// This side's list was a prefix of the other one.
+        RanOut(Side),
+    }
+
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    pub(crate) enum Event {
+        Compare {
+            left: Signal,
+            right: Signal,
+        },
+        /// The integer on `side` was compared as the one-element list `promoted`.
+        Promote {
+            side: Side,
+            promoted: Signal,
+        },
+        Decided(Reason),
+    }
+
+    /// One line of a trace. `path` holds the list indices leading to the values being compared,
+    /// and `depth` the indentation, which also grows when an integer is promoted.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    pub(crate) struct Step {
+        pub(crate) depth: usize,
+        pub(crate) path: Vec<usize>,
+        pub(crate) event: Event,
+    }
+
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    pub(crate) struct Trace {
+        pub(crate) ordering: Ordering,
+        pub(crate) steps: Vec<Step>,
+    }
+
+    impl Trace {
+        /// The step that settled the comparison, if the signals aren't equal.
+        pub(crate) fn decision(&self) -> Option<&Step> {
+            self.steps
+                .last()
+                .filter(|step| matches!(step.event, Event::Decided(_)))
+        }
+    }
+
+    /// Compare like `Signal::cmp`, recording every step.
+    pub(crate) fn compare(left: &Signal, right: &Signal) -> Trace {
+        let mut steps = vec![];
+        let ordering = walk(left, right, 0, &mut vec![], &mut steps);
+        Trace { ordering, steps }
+    }
+
+    fn walk(
+        left: &Signal,
+        right: &Signal,
+        depth: usize,
+        path: &mut Vec<usize>,
+        steps: &mut Vec<Step>,
+    ) -> Ordering {
+        let mut push = |depth, event| {
+            steps.push(Step {
+                depth,
+                path: path.clone(),
+                event,
+            })
+        };
+        push(
+            depth,
+            Event::Compare {
+                left: left.clone(),
+                right: right.clone(),
+            },
+        );
+        match (left, right) {
+            (Signal::Integer(l), Signal::Integer(r)) => {
+                let ordering = l.cmp(r);
+                match ordering {
+                    Ordering::Less => push(depth + 1, Event::Decided(Reason::Smaller(Side::Left))),
+                    Ordering::Greater => {
+                        push(depth + 1, Event::Decided(Reason::Smaller(Side::Right)))
+                    }
+                    Ordering::Equal => (),
+                }
+                ordering
+            }
+            (&Signal::Integer(l), Signal::List(_)) => {
+                let promoted = Signal::List(vec![Signal::Integer(l)]);
+                push(
+                    depth + 1,
+                    Event::Promote {
+                        side: Side::Left,
+                        promoted: promoted.clone(),
+                    },
+                );
+                walk(&promoted, right, depth + 1, path, steps)
+            }
+            (Signal::List(_), &Signal::Integer(r)) => {
+                let promoted = Signal::List(vec![Signal::Integer(r)]);
+                push(
+                    depth + 1,
+                    Event::Promote {
+                        side: Side::Right,
+                        promoted: promoted.clone(),
+                    },
+                );
+                walk(left, &promoted, depth + 1, path, steps)
+            }
+            (Signal::List(l), Signal::List(r)) => {
+                for (i, (l, r)) in l.iter().zip(r).enumerate() {
+                    path.push(i);
+                    let ordering = walk(l, r, depth + 1, path, steps);
+                    path.pop();
+                    if ordering.is_ne() {
+                        return ordering;
+                    }
+                }
+                let ordering = l.len().cmp(&r.len());
+                let ran_out = match ordering {
+                    Ordering::Less => Some(Side::Left),
+                    Ordering::Greater => Some(Side::Right),
+                    Ordering::Equal => None,
+                };
+                if let Some(side) = ran_out {
+                    steps.push(Step {
+                        depth: depth + 1,
+                        path: path.clone(),
+                        event: Event::Decided(Reason::RanOut(side)),
+                    });
+                }
+                ordering
+            }
+        }
+    }
+
+    impl std::fmt::Display for Side {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Side::Left => write!(f, "left"),
+                Side::Right => write!(f, "right"),
+            }
+        }
+    }
+
+    /// Uses the wording of the puzzle's worked example.
+    impl std::fmt::Display for Event {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Event::Compare { left, right } => write!(f, "Compare {left} vs {right}"),
+                Event::Promote { side, promoted } => write!(
+                    f,
+                    "Mixed types; convert {side} to {promoted} and retry comparison"
+                ),
+                Event::Decided(Reason::Smaller(Side::Left)) => {
+                    write!(f, "Left side is smaller, so input is in the right order")
+                }
+                Event::Decided(Reason::Smaller(Side::Right)) => {
+                    write!(
+                        f,
+                        "Right side is smaller, so input is not in the right order"
+                    )
+                }
+                Event::Decided(Reason::RanOut(Side::Left)) => {
+                    write!(
+                        f,
+                        "Left side ran out of items, so inputs are in the right order"
+                    )
+                }
+                Event::Decided(Reason::RanOut(Side::Right)) => write!(
+                    f,
+                    "Right side ran out of items, so inputs are not in the right order"
+                ),
+            }
+        }
+    }
+
+    impl std::fmt::Display for Trace {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            for step in &self.steps {
+                writeln!(f, "{:indent$}- {}", "", step.event, indent = 2 * step.depth)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Compares packets straight from their text, without building `Signal`s.
+mod stream {
+    use std::cmp::Ordering;
+
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    enum Token {
+        Open,
+        Close,
+        Integer(u64),
+    }
+
+    /// Reads tokens off a packet, skipping commas and whitespace.
+    #[derive(Copy, Clone, Debug)]
+    struct Cursor<'a> {
+        s: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Cursor<'a> {
+        fn new(s: &'a str) -> Self {
+            Self {
+                s: s.as_bytes(),
+                pos: 0,
+            }
+        }
+
+        /// `None` at the end of the input, and on anything that isn't a token.
+        fn next(&mut self) -> Option<Token> {
+            while self
+                .s
+                .get(self.pos)
+                .is_some_and(|b| *b == b',' || b.is_ascii_whitespace())
+            {
+                self.pos += 1;
+            }
+            let token = match *self.s.get(self.pos)? {
+                b'[' => Token::Open,
+                b']' => Token::Close,
+                b'0'..=b'9' => {
+                    let mut val = 0u64;
+                    while let Some(&b @ b'0'..=b'9') = self.s.get(self.pos) {
+                        val = val.checked_mul(10)?.checked_add(u64::from(b - b'0'))?;
+                        self.pos += 1;
+                    }
+                    return Some(Token::Integer(val));
+                }
+                _ => return None,
+            };
+            self.pos += 1;
+            Some(token)
+        }
+
+        fn peek(&self) -> Option<Token> {
+            let mut ahead = *self;
+            ahead.next()
+        }
+    }
+
+    /// Compare two packets like `Signal::cmp` would compare their parsed forms. Gives `None` on
+    /// malformed input, but only as far as it has to read to decide.
+    pub(crate) fn compare(left: &str, right: &str) -> Option<Ordering> {
+        compare_values(&mut Cursor::new(left), &mut Cursor::new(right))
+    }
+
+    fn compare_values(left: &mut Cursor, right: &mut Cursor) -> Option<Ordering> {
+        match (left.next()?, right.next()?) {
+            (Token::Integer(l), Token::Integer(r)) => Some(l.cmp(&r)),
+            (Token::Open, Token::Open) => compare_lists(left, right),
+            (Token::Integer(l), Token::Open) => compare_integer_to_list(l, right),
+            (Token::Open, Token::Integer(r)) => {
+                compare_integer_to_list(r, left).map(Ordering::reverse)
+            }
+            (Token::Close, _) | (_, Token::Close) => None,
+        }
+    }
+
+    /// Both cursors are right after a `[`.
+    fn compare_lists(left: &mut Cursor, right: &mut Cursor) -> Option<Ordering> {
+        loop {
+            match (left.peek()?, right.peek()?) {
+                (Token::Close, Token::Close) => {
+                    left.next();
+                    right.next();
+                    return Some(Ordering::Equal);
+                }
+                (Token::Close, _) => return Some(Ordering::Less),
+                (_, Token::Close) => return Some(Ordering::Greater),
+                _ => match compare_values(left, right)? {
+                    Ordering::Equal => (),
+                    ordering => return Some(ordering),
+                },
+            }
+        }
+    }
+
+    /// Compare `l` as if it were `[l]` to the list `right` is right after the `[` of.
+    fn compare_integer_to_list(l: u64, right: &mut Cursor) -> Option<Ordering> {
+        let ordering = match right.next()? {
+            // `[l]` is longer than `[]`.
+            Token::Close => return Some(Ordering::Greater),
+            Token::Integer(r) => l.cmp(&r),
+            Token::Open => compare_integer_to_list(l, right)?,
+        };
+        if ordering.is_ne() {
+            return Some(ordering);
+        }
+        match right.next()? {
+            Token::Close => Some(Ordering::Equal),
+            // `[l]` ran out of items first.
+            _ => Some(Ordering::Less),
+        }
+    }
+
+    /// Sum of the 1-based indices of pairs in the right order.
+    pub(crate) fn q1<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Option<usize> {
+        let mut sum = 0;
+        for ((left, right), i) in pairs.into_iter().zip(1..) {
+            if compare(left, right)?.is_le() {
+                sum += i;
+            }
+        }
+        Some(sum)
+    }
+
+    /// Product of the 1-based positions the dividers would have if sorted in with the packets.
+    /// Rather than sorting, counts the packets that would come before each divider. The dividers
+    /// must be in order already.
+    pub(crate) fn q2<'a>(
+        packets: impl IntoIterator<Item = &'a str>,
+        dividers: &[&str],
+    ) -> Option<usize> {
+        let mut before = vec![0; dividers.len()];
+        for packet in packets {
+            for (count, divider) in before.iter_mut().zip(dividers) {
+                if compare(packet, divider)?.is_lt() {
+                    *count += 1;
+                }
+            }
+        }
+        Some(before.iter().zip(1..).map(|(count, i)| count + i).product())
     }
 }
 
-#[allow(unused)]
 mod parse {
     use super::*;
     use nom::{
         branch::alt,
-        character::complete::{char, i32, multispace0, newline},
-        combinator::map,
+        character::complete::{char, multispace0, newline, space0, u64},
+        combinator::{all_consuming, map},
         multi::separated_list0,
         sequence::{delimited, pair, separated_pair, terminated},
         Finish, IResult,
@@ -37,13 +458,24 @@ mod parse {
 
     type ParseResult<'a, T> = Result<T, nom::error::Error<String>>;
 
+    /// Allows spaces around `inner`, like the tokenizer in [`my_parse`].
+    fn ws<'a, O>(
+        inner: impl FnMut(&'a str) -> IResult<&'a str, O>,
+    ) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+        delimited(space0, inner, space0)
+    }
+
     fn integer(s: &str) -> IResult<&str, Signal> {
-        map(i32, Signal::Integer)(s)
+        map(ws(u64), Signal::Integer)(s)
     }
 
     fn list(s: &str) -> IResult<&str, Signal> {
         map(
-            delimited(char('['), separated_list0(char(','), signal), char(']')),
+            delimited(
+                ws(char('[')),
+                separated_list0(ws(char(',')), signal),
+                ws(char(']')),
+            ),
             Signal::List,
         )(s)
     }
@@ -52,20 +484,21 @@ mod parse {
         alt((integer, list))(s)
     }
 
-    pub(super) fn parse_signal(s: &str) -> ParseResult<Signal> {
-        signal(s)
+    #[cfg(test)]
+    pub(super) fn parse_signal(s: &str) -> ParseResult<'_, Signal> {
+        all_consuming(signal)(s)
             .map_err(|e| e.to_owned())
             .finish()
             .map(|(_, out)| out)
     }
-    pub(super) fn parse(s: &str) -> ParseResult<Vec<(Signal, Signal)>> {
-        terminated(
+    pub(super) fn parse(s: &str) -> ParseResult<'_, Vec<(Signal, Signal)>> {
+        all_consuming(terminated(
             separated_list0(
                 pair(newline, newline),
                 separated_pair(signal, newline, signal),
             ),
             multispace0,
-        )(s)
+        ))(s)
         .map_err(|e| e.to_owned())
         .finish()
         .map(|(_, out)| out)
@@ -80,107 +513,168 @@ mod my_parse {
     pub enum ParseError {
         #[error("Unexpected end of input")]
         Eof,
-        #[error("Unexpected Token: {0:?}")]
-        UnexpectedToken(Token),
+        #[error("column {col}: Unexpected Token: {token:?}")]
+        UnexpectedToken { col: usize, token: Token },
         #[error("Expected empty line")]
         ExpectEmptyLine,
+        #[error("Expected a packet but found an empty line")]
+        ExpectPacket,
+        #[error("column {col}: Expected ',' or ']' but found {token:?}")]
+        MissingComma { col: usize, token: Token },
+        #[error("column {col}: Integer {digits} does not fit in 64 bits")]
+        IntegerOverflow { col: usize, digits: String },
+        #[error("column {col}: Unexpected {token:?} after the end of the packet")]
+        TrailingInput { col: usize, token: Token },
+        #[error("line {line}: {source}")]
+        Line {
+            line: usize,
+            source: Box<ParseError>,
+        },
     }
 
     pub type ParseResult<T> = Result<T, ParseError>;
+
+    /// How forgiving the parser is.
+    #[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+    pub enum Mode {
+        /// Accept a missing comma between list items, and ignore anything after a packet.
+        #[default]
+        Lenient,
+        Strict,
+    }
+
     #[derive(Eq, PartialEq, Debug)]
     pub enum Token {
         LeftBracket,
         RightBracket,
-        Integer(i32),
+        Integer(u64),
         Comma,
         Unrecognized(char),
+        /// Digits of an integer too large for `u64`.
+        Overflow(String),
     }
 
-    type TokenStream<'a> = std::iter::Peekable<Box<dyn Iterator<Item = Token> + 'a>>;
+    /// Tokens along with their 1-based column.
+    type TokenStream<'a> = std::iter::Peekable<Box<dyn Iterator<Item = (usize, Token)> + 'a>>;
 
-    fn tokens(s: &str) -> TokenStream {
-        let mut chars = s.chars().peekable();
+    fn spanned_tokens(s: &str) -> TokenStream<'_> {
+        let mut chars = s.chars().zip(1..).peekable();
         let token_stream = std::iter::from_fn(move || {
-            while let Some(_) = chars.next_if(|c| c.is_whitespace()) {}
-            let token = match chars.next()? {
+            while chars.next_if(|(c, _)| c.is_whitespace()).is_some() {}
+            let (c, col) = chars.next()?;
+            let token = match c {
                 '[' => Token::LeftBracket,
                 ']' => Token::RightBracket,
                 ',' => Token::Comma,
                 c if c.is_ascii_digit() => {
-                    fn to_digit(c: char) -> i32 {
-                        c.to_digit(10)
-                            .expect("This cannot fail because we already checked ascii_digit")
-                            as i32
+                    let mut digits = c.to_string();
+                    while let Some((c, _)) = chars.next_if(|(c, _)| c.is_ascii_digit()) {
+                        digits.push(c);
                     }
-                    let mut val = to_digit(c);
-                    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
-                        val *= 10;
-                        val += to_digit(c);
+                    // These are all digits, so this can only fail by overflowing.
+                    match digits.parse() {
+                        Ok(val) => Token::Integer(val),
+                        Err(_) => Token::Overflow(digits),
                     }
-                    Token::Integer(val)
                 }
                 c => Token::Unrecognized(c),
             };
 
-            Some(token)
+            Some((col, token))
         });
-        let token_stream: Box<dyn Iterator<Item = Token>> = Box::new(token_stream);
+        let token_stream: Box<dyn Iterator<Item = (usize, Token)>> = Box::new(token_stream);
         token_stream.peekable()
     }
 
-    fn signal<'s, 't: 's>(tok: &'s mut TokenStream<'t>) -> ParseResult<Signal> {
-        fn token_items(tok: &mut TokenStream) -> ParseResult<Vec<Signal>> {
+    fn tokens(s: &str) -> impl Iterator<Item = Token> + '_ {
+        spanned_tokens(s).map(|(_, token)| token)
+    }
+
+    fn signal(tok: &mut TokenStream<'_>, mode: Mode) -> ParseResult<Signal> {
+        fn token_items(tok: &mut TokenStream, mode: Mode) -> ParseResult<Vec<Signal>> {
             // consume ']' or a signal,
             // then consume '[,signal]'
-            if let Some(&Token::RightBracket) = tok.peek() {
+            if let Some((_, Token::RightBracket)) = tok.peek() {
                 let _ = tok.next();
                 return Ok(vec![]);
             }
             let mut signals = vec![];
             loop {
-                signals.push(signal(tok)?);
+                signals.push(signal(tok, mode)?);
                 match tok.peek() {
-                    Some(&Token::RightBracket) => {
+                    Some((_, Token::RightBracket)) => {
                         let _ = tok.next();
                         return Ok(signals);
                     }
-                    Some(&Token::Comma) => {
+                    Some((_, Token::Comma)) => {
                         let _ = tok.next();
                     }
-                    Some(_) => (),
+                    Some(_) if mode == Mode::Lenient => (),
+                    Some(_) => {
+                        let (col, token) = tok.next().expect("just peeked");
+                        return Err(ParseError::MissingComma { col, token });
+                    }
                     None => return Err(ParseError::Eof),
                 }
             }
         }
         match tok.next() {
             None => Err(ParseError::Eof),
-            Some(Token::LeftBracket) => Ok(Signal::List(token_items(tok)?)),
-            Some(Token::Integer(i)) => Ok(Signal::Integer(i)),
-            Some(tok) => Err(ParseError::UnexpectedToken(tok)),
+            Some((_, Token::LeftBracket)) => Ok(Signal::List(token_items(tok, mode)?)),
+            Some((_, Token::Integer(i))) => Ok(Signal::Integer(i)),
+            Some((col, Token::Overflow(digits))) => {
+                Err(ParseError::IntegerOverflow { col, digits })
+            }
+            Some((col, token)) => Err(ParseError::UnexpectedToken { col, token }),
         }
     }
 
     pub(super) fn parse_signal(s: &str) -> ParseResult<Signal> {
-        let mut token_stream = tokens(s);
-        signal(&mut token_stream)
+        parse_signal_with(s, Mode::Lenient)
+    }
+
+    pub(super) fn parse_signal_with(s: &str, mode: Mode) -> ParseResult<Signal> {
+        let mut token_stream = spanned_tokens(s);
+        let signal = signal(&mut token_stream, mode)?;
+        match token_stream.next() {
+            Some((col, token)) if mode == Mode::Strict => {
+                Err(ParseError::TrailingInput { col, token })
+            }
+            _ => Ok(signal),
+        }
     }
 
     pub(super) fn parse(s: &str) -> ParseResult<Vec<(Signal, Signal)>> {
-        let mut lines = s.lines();
+        parse_with(s, Mode::Lenient)
+    }
+
+    pub(super) fn parse_with(s: &str, mode: Mode) -> ParseResult<Vec<(Signal, Signal)>> {
+        let at_line = |line| {
+            move |e| ParseError::Line {
+                line,
+                source: Box::new(e),
+            }
+        };
+        let mut lines = s.lines().zip(1..);
         let mut out = vec![];
         loop {
-            let fst = {
-                let line = match lines.next() {
-                    Some(x) => x,
-                    None => return Ok(out),
-                };
-                if line.trim().is_empty() {
-                    return Ok(out);
-                }
-                parse_signal(line)?
+            let (line, n) = match lines.next() {
+                Some(x) => x,
+                None => return Ok(out),
             };
-            let snd = parse_signal(lines.next().ok_or(ParseError::Eof)?)?;
-            let sep = match lines.next() {
+            if line.trim().is_empty() {
+                // Only the lenient parser treats a blank line as the end of the input.
+                return match mode {
+                    Mode::Lenient => Ok(out),
+                    Mode::Strict => Err(at_line(n)(ParseError::ExpectPacket)),
+                };
+            }
+            let fst = parse_signal_with(line, mode).map_err(at_line(n))?;
+            let (line, n) = lines
+                .next()
+                .ok_or_else(|| at_line(n + 1)(ParseError::Eof))?;
+            let snd = parse_signal_with(line, mode).map_err(at_line(n))?;
+            let (sep, n) = match lines.next() {
                 Some(x) => x,
                 None => {
                     out.push((fst, snd));
@@ -188,7 +682,7 @@ mod my_parse {
                 }
             };
             if !sep.trim().is_empty() {
-                return Err(ParseError::ExpectEmptyLine);
+                return Err(at_line(n)(ParseError::ExpectEmptyLine));
             }
             out.push((fst, snd));
         }
@@ -258,8 +752,71 @@ mod my_parse {
 }
 
 fn main() -> anyhow::Result<()> {
+    const USAGE: &str =
+        "usage: aoc_22_13 [--strict | --nom] [--trace PAIR[,PAIR...]] [--distinct] [--streaming] \
+         < input";
+    let mut mode = my_parse::Mode::Lenient;
+    let mut nom = false;
+    let mut streaming = false;
+    let mut distinct = false;
+    let mut traced = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strict" => mode = my_parse::Mode::Strict,
+            "--nom" => nom = true,
+            "--streaming" => streaming = true,
+            "--distinct" => distinct = true,
+            "--trace" => {
+                let pairs = args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                for pair in pairs.split(',') {
+                    traced.push(pair.parse::<usize>()?);
+                }
+            }
+            _ => anyhow::bail!(USAGE),
+        }
+    }
     let input = std::io::read_to_string(std::io::stdin())?;
-    let input = my_parse::parse(input.as_str())?;
+    if streaming {
+        let packets = input
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        anyhow::ensure!(packets.len() % 2 == 0, "odd number of packets");
+        let malformed = || anyhow::anyhow!("malformed packet");
+        let pairs = packets.chunks(2).map(|pair| (pair[0], pair[1]));
+        println!("q1: {}", stream::q1(pairs).ok_or_else(malformed)?);
+        let q2 = stream::q2(packets.iter().copied(), &["[[2]]", "[[6]]"]);
+        println!("q2: {}", q2.ok_or_else(malformed)?);
+        return Ok(());
+    }
+    let input = if nom {
+        parse::parse(input.as_str())?
+    } else {
+        my_parse::parse_with(input.as_str(), mode)?
+    };
+
+    for i in traced {
+        let (x, y) = i
+            .checked_sub(1)
+            .and_then(|i| input.get(i))
+            .ok_or_else(|| anyhow::anyhow!("there is no pair {i}"))?;
+        let trace = trace::compare(x, y);
+        println!("== Pair {i} ==\n{trace}");
+        if let Some(step) = trace.decision() {
+            println!("Decided at index path {:?}\n", step.path);
+        }
+    }
+    if distinct {
+        let packets = input
+            .iter()
+            .flat_map(|(x, y)| [x, y])
+            .cloned()
+            .map(Canonical::from)
+            .collect::<std::collections::HashSet<_>>();
+        println!("distinct packets: {}", packets.len());
+    }
 
     println!(
         "q1: {}",
@@ -270,8 +827,8 @@ fn main() -> anyhow::Result<()> {
             .sum::<usize>()
     );
     let sentinels = [
-        my_parse::parse_signal("[[2]]")?,
-        my_parse::parse_signal("[[6]]")?,
+        Signal::builder().list(|l| l.int(2)).build(),
+        Signal::builder().list(|l| l.int(6)).build(),
     ];
     let mut input = input
         .into_iter()
@@ -289,3 +846,325 @@ fn main() -> anyhow::Result<()> {
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+    use std::cmp::Ordering;
+    use std::collections::HashSet;
+
+    fn arb_signal() -> impl Strategy<Value = Signal> {
+        (0..4u64)
+            .prop_map(Signal::Integer)
+            .prop_recursive(4, 24, 4, |inner| {
+                prop::collection::vec(inner, 0..4).prop_map(Signal::List)
+            })
+    }
+
+    #[test]
+    fn test_display() {
+        for s in [
+            "[1,[2,[3,[4,[5,6,7]]]],8,9]",
+            "[]",
+            "[[[]]]",
+            "12",
+            "[10,[],[0]]",
+        ] {
+            assert_eq!(my_parse::parse_signal(s).unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        let built = Signal::builder()
+            .int(1)
+            .list(|l| l.int(2).list(|l| l).push(3))
+            .build();
+        assert_eq!(built.to_string(), "[1,[2,[],3]]");
+        assert_eq!(Signal::builder().build(), Signal::List(vec![]));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let normalized = |s: &str| my_parse::parse_signal(s).unwrap().normalize().to_string();
+        assert_eq!(normalized("[[[2]]]"), "2");
+        assert_eq!(normalized("[1,[2],[[]],[[3,4]]]"), "[1,2,[[]],[[3,4]]]");
+        assert_eq!(normalized("[[]]"), "[[]]");
+    }
+
+    #[test]
+    fn test_canonical_dedup() {
+        let packets = ["[[2]]", "2", "[2]", "[[2],3]", "[2,[3]]", "[]"]
+            .map(|s| Canonical::from(my_parse::parse_signal(s).unwrap()));
+        let distinct: HashSet<_> = packets.iter().collect();
+        assert_eq!(distinct.len(), 3);
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        use my_parse::{Mode, ParseError, Token};
+        let strict = |s| my_parse::parse_signal_with(s, Mode::Strict);
+        assert_eq!(
+            my_parse::parse_signal("[1 2]").unwrap().to_string(),
+            "[1,2]"
+        );
+        assert_eq!(
+            strict("[1 2]"),
+            Err(ParseError::MissingComma {
+                col: 4,
+                token: Token::Integer(2)
+            })
+        );
+        assert_eq!(
+            strict("[[1] [2]]"),
+            Err(ParseError::MissingComma {
+                col: 6,
+                token: Token::LeftBracket
+            })
+        );
+        assert!(my_parse::parse_signal("[1] 2").is_ok());
+        assert_eq!(
+            strict("[1] 2"),
+            Err(ParseError::TrailingInput {
+                col: 5,
+                token: Token::Integer(2)
+            })
+        );
+        assert_eq!(strict("[ 1, 2 ]").unwrap().to_string(), "[1,2]");
+        assert_eq!(
+            parse::parse_signal(" [ 1, 2 ] ").unwrap().to_string(),
+            "[1,2]"
+        );
+    }
+
+    #[test]
+    fn test_large_integers() {
+        use my_parse::ParseError;
+        let max = u64::MAX.to_string();
+        let s = format!("[{max}]");
+        assert_eq!(
+            my_parse::parse_signal(&s),
+            Ok(Signal::List(vec![Signal::Integer(u64::MAX)]))
+        );
+        assert_eq!(
+            parse::parse_signal(&s).unwrap(),
+            Signal::List(vec![Signal::Integer(u64::MAX)])
+        );
+        let too_large = format!("[1,{max}0]");
+        assert_eq!(
+            my_parse::parse_signal(&too_large),
+            Err(ParseError::IntegerOverflow {
+                col: 4,
+                digits: format!("{max}0")
+            })
+        );
+        assert!(parse::parse_signal(&too_large).is_err());
+    }
+
+    #[test]
+    fn test_error_lines() {
+        let err = my_parse::parse_with("[1]\n[2]\n\n[3]\n[4 5]\n", my_parse::Mode::Strict);
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "line 5: column 4: Expected ',' or ']' but found Integer(5)"
+        );
+        let err = my_parse::parse("[1]\n[2]\n[3]\n");
+        assert_eq!(err.unwrap_err().to_string(), "line 3: Expected empty line");
+        let err = my_parse::parse("[1]\n[2]\n\n[3]\n");
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "line 5: Unexpected end of input"
+        );
+        let err = my_parse::parse_with("[1]\n[2]\n\n\n[3]\n[4]\n", my_parse::Mode::Strict);
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "line 4: Expected a packet but found an empty line"
+        );
+        let lenient = my_parse::parse("[1]\n[2]\n\n\n[3]\n[4]\n").unwrap();
+        assert_eq!(lenient.len(), 1);
+    }
+
+    const SAMPLE_TRACES: &str = "\
+== Pair 1 ==
+- Compare [1,1,3,1,1] vs [1,1,5,1,1]
+  - Compare 1 vs 1
+  - Compare 1 vs 1
+  - Compare 3 vs 5
+    - Left side is smaller, so input is in the right order
+
+== Pair 2 ==
+- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so input is in the right order
+
+== Pair 3 ==
+- Compare [9] vs [[8,7,6]]
+  - Compare 9 vs [8,7,6]
+    - Mixed types; convert left to [9] and retry comparison
+    - Compare [9] vs [8,7,6]
+      - Compare 9 vs 8
+        - Right side is smaller, so input is not in the right order
+
+== Pair 4 ==
+- Compare [[4,4],4,4] vs [[4,4],4,4,4]
+  - Compare [4,4] vs [4,4]
+    - Compare 4 vs 4
+    - Compare 4 vs 4
+  - Compare 4 vs 4
+  - Compare 4 vs 4
+  - Left side ran out of items, so inputs are in the right order
+
+== Pair 5 ==
+- Compare [7,7,7,7] vs [7,7,7]
+  - Compare 7 vs 7
+  - Compare 7 vs 7
+  - Compare 7 vs 7
+  - Right side ran out of items, so inputs are not in the right order
+
+== Pair 6 ==
+- Compare [] vs [3]
+  - Left side ran out of items, so inputs are in the right order
+
+== Pair 7 ==
+- Compare [[[]]] vs [[]]
+  - Compare [[]] vs []
+    - Right side ran out of items, so inputs are not in the right order
+
+== Pair 8 ==
+- Compare [1,[2,[3,[4,[5,6,7]]]],8,9] vs [1,[2,[3,[4,[5,6,0]]]],8,9]
+  - Compare 1 vs 1
+  - Compare [2,[3,[4,[5,6,7]]]] vs [2,[3,[4,[5,6,0]]]]
+    - Compare 2 vs 2
+    - Compare [3,[4,[5,6,7]]] vs [3,[4,[5,6,0]]]
+      - Compare 3 vs 3
+      - Compare [4,[5,6,7]] vs [4,[5,6,0]]
+        - Compare 4 vs 4
+        - Compare [5,6,7] vs [5,6,0]
+          - Compare 5 vs 5
+          - Compare 6 vs 6
+          - Compare 7 vs 0
+            - Right side is smaller, so input is not in the right order
+";
+
+    #[test]
+    fn test_sample_traces() {
+        let input = my_parse::parse(include_str!("../sample_input.txt")).unwrap();
+        let traces = input
+            .iter()
+            .zip(1..)
+            .map(|((x, y), i)| format!("== Pair {i} ==\n{}", trace::compare(x, y)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(traces, SAMPLE_TRACES);
+    }
+
+    #[test]
+    fn test_trace_decision() {
+        use trace::{Event, Reason, Side};
+        let input = my_parse::parse(include_str!("../sample_input.txt")).unwrap();
+        let (x, y) = &input[7];
+        let decision = trace::compare(x, y).decision().cloned().unwrap();
+        assert_eq!(decision.path, [1, 1, 1, 1, 2]);
+        assert_eq!(decision.event, Event::Decided(Reason::Smaller(Side::Right)));
+        let (x, y) = &input[2];
+        let decision = trace::compare(x, y).decision().cloned().unwrap();
+        assert_eq!(decision.path, [0, 0]);
+        let same = my_parse::parse_signal("[[1],2]").unwrap();
+        assert_eq!(trace::compare(&same, &same).decision(), None);
+    }
+
+    #[test]
+    fn test_stream_sample() {
+        let input = include_str!("../sample_input.txt");
+        let packets = input.lines().filter(|l| !l.is_empty()).collect::<Vec<_>>();
+        let pairs = packets.chunks(2).map(|p| (p[0], p[1]));
+        assert_eq!(stream::q1(pairs), Some(13));
+        assert_eq!(
+            stream::q2(packets.iter().copied(), &["[[2]]", "[[6]]"]),
+            Some(140)
+        );
+        assert_eq!(stream::compare("[1, [2]]", "[1,2]"), Some(Ordering::Equal));
+        assert_eq!(stream::compare("[1,x]", "[1,2]"), None);
+        assert_eq!(stream::compare("[[", "[[1]]"), None);
+    }
+
+    /// Signals with integers anywhere in the `u64` range.
+    fn arb_wide_signal() -> impl Strategy<Value = Signal> {
+        prop_oneof![0..20u64, any::<u64>()]
+            .prop_map(Signal::Integer)
+            .prop_recursive(5, 40, 5, |inner| {
+                prop::collection::vec(inner, 0..5).prop_map(Signal::List)
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn test_parsers_agree_on_packets(s in arb_wide_signal()) {
+            let text = s.to_string();
+            prop_assert_eq!(my_parse::parse_signal_with(&text, my_parse::Mode::Strict), Ok(s.clone()));
+            prop_assert_eq!(parse::parse_signal(&text).unwrap(), s);
+        }
+
+        #[test]
+        fn test_parsers_agree_on_files(
+            pairs in prop::collection::vec((arb_wide_signal(), arb_wide_signal()), 0..5)
+        ) {
+            let text: String = pairs.iter().map(|(a, b)| format!("{a}\n{b}\n\n")).collect();
+            prop_assert_eq!(my_parse::parse_with(&text, my_parse::Mode::Strict), Ok(pairs.clone()));
+            prop_assert_eq!(parse::parse(&text).unwrap(), pairs);
+        }
+
+        #[test]
+        fn test_parsers_agree_on_noise(s in "[\\[\\], 0-9]{0,12}|[\\[\\], 9]{20,30}") {
+            let strict = my_parse::parse_signal_with(&s, my_parse::Mode::Strict);
+            let nom = parse::parse_signal(&s);
+            prop_assert_eq!(strict.is_ok(), nom.is_ok(), "{:?} {:?}", strict, nom);
+            if let (Ok(a), Ok(b)) = (strict, nom) {
+                prop_assert_eq!(a, b);
+            }
+        }
+
+        #[test]
+        fn test_stream_matches_cmp(a in arb_wide_signal(), b in arb_wide_signal()) {
+            prop_assert_eq!(stream::compare(&a.to_string(), &b.to_string()), Some(a.cmp(&b)));
+        }
+
+        #[test]
+        fn test_stream_matches_cmp_on_close_signals(a in arb_signal(), b in arb_signal()) {
+            prop_assert_eq!(stream::compare(&a.to_string(), &b.to_string()), Some(a.cmp(&b)));
+        }
+
+        #[test]
+        fn test_trace_matches_cmp(a in arb_signal(), b in arb_signal()) {
+            let trace = trace::compare(&a, &b);
+            prop_assert_eq!(trace.ordering, a.cmp(&b));
+            prop_assert_eq!(trace.decision().is_some(), trace.ordering.is_ne());
+        }
+
+        #[test]
+        fn test_display_round_trip(s in arb_signal()) {
+            let printed = s.to_string();
+            prop_assert_eq!(my_parse::parse_signal(&printed), Ok(s.clone()));
+            prop_assert_eq!(parse::parse_signal(&printed).unwrap(), s);
+        }
+
+        #[test]
+        fn test_normalize_keeps_order(a in arb_signal(), b in arb_signal()) {
+            prop_assert_eq!(a.normalize().cmp(&b.normalize()), a.cmp(&b));
+            prop_assert_eq!(a.normalize().cmp(&a), Ordering::Equal);
+        }
+
+        #[test]
+        fn test_canonical_matches_order(a in arb_signal(), b in arb_signal()) {
+            let (ca, cb) = (Canonical::from(a.clone()), Canonical::from(b.clone()));
+            prop_assert_eq!(ca == cb, a.cmp(&b) == Ordering::Equal);
+            prop_assert_eq!(ca.cmp(&cb), a.cmp(&b));
+        }
+    }
+}
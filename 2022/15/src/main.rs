@@ -4,9 +4,38 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 
 type Coordinate = (i64, i64);
+/// A sensor and its closest beacon.
+type Report = (Coordinate, Coordinate);
 
-const TARGET_ROW: i64 = 2000_000;
-const MAX_BEACON_VAL: i64 = 4000_000;
+/// Where to look: the row to count in part 1, and the largest coordinate the distress beacon can
+/// have in part 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bounds {
+    row: i64,
+    max: i64,
+}
+
+impl Bounds {
+    const PUZZLE: Bounds = Bounds {
+        row: 2_000_000,
+        max: 4_000_000,
+    };
+    const SAMPLE: Bounds = Bounds { row: 10, max: 20 };
+
+    /// Apply whitespace separated `row=N` and `max=N` settings.
+    fn apply(&mut self, settings: &str) -> anyhow::Result<()> {
+        for setting in settings.split_whitespace() {
+            match setting.split_once('=') {
+                Some(("row", n)) => self.row = n.parse()?,
+                Some(("max", n)) => self.max = n.parse()?,
+                _ => anyhow::bail!("Bad setting {setting:?}, expected row=N or max=N"),
+            }
+        }
+        Ok(())
+    }
+}
+
+const TUNING_MULTIPLIER: i64 = 4_000_000;
 
 #[derive(Debug, thiserror::Error)]
 enum ParseError {
@@ -62,16 +91,14 @@ impl Intervals {
                 // do nothing, there's no overlap.
             }
         }
-        if let Some(next @ (min, max)) = self.0.range((left, right)..).copied().next() {
-            // min >= left
-            // check the position of `min` inside the interval [`left`, `right`]
-            if min <= right {
-                // merge the two intervals
-                self.0.remove(&next);
-                right = right.max(max);
-            } else {
-                // do nothing, there's no overlap.
+        // min >= left for every following interval; merge all of those that start inside
+        // [`left`, `right`].
+        while let Some(next @ (min, max)) = self.0.range((left, right)..).copied().next() {
+            if min > right {
+                break; // no overlap
             }
+            self.0.remove(&next);
+            right = right.max(max);
         }
         self.0.insert((left, right));
     }
@@ -122,10 +149,7 @@ fn distance((x1, y1): Coordinate, (x2, y2): Coordinate) -> u64 {
 
 fn disallowed_x_at_y(sensor: Coordinate, beacon: Coordinate, y: i64) -> Option<(i64, i64)> {
     let dis = distance(sensor, beacon);
-    let allowed_x_dis = match dis.checked_sub(sensor.1.abs_diff(y)) {
-        Some(i) => i,
-        None => return None,
-    };
+    let allowed_x_dis = dis.checked_sub(sensor.1.abs_diff(y))?;
     let x_delta = i64::try_from(allowed_x_dis).expect("distance cannot fit in an `i64`");
     Some((sensor.0 - x_delta, sensor.0 + x_delta))
 }
@@ -142,7 +166,7 @@ fn count_impossible_positions_at_row(input: &[(Coordinate, Coordinate)], row: i6
         let beacons = input
             .iter()
             .copied()
-            .filter_map(|(_sensor, _beacon @ (x, y))| (y == TARGET_ROW).then_some(x))
+            .filter_map(|(_sensor, _beacon @ (x, y))| (y == row).then_some(x))
             .collect::<std::collections::HashSet<_>>();
         beacons.len()
     };
@@ -167,21 +191,849 @@ fn find_beacon(input: &[(Coordinate, Coordinate)], max_val: i64) -> (i64, i64) {
     candidates.into_iter().next().expect("Impossible")
 }
 
-fn main() -> anyhow::Result<()> {
-    let input = std::io::read_to_string(std::io::stdin())?;
-    let input = input
-        .lines()
+/// The sensor field as a whole, worked on in rotated coordinates `u = x + y`, `v = x - y`, where
+/// every sensor's diamond becomes an axis-aligned rectangle.
+mod geometry {
+    use super::{distance, Coordinate, Intervals, Report};
+    use itertools::Itertools;
+
+    /// An inclusive box of cells.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub(crate) struct BoundingBox {
+        pub(crate) min: Coordinate,
+        pub(crate) max: Coordinate,
+    }
+
+    impl BoundingBox {
+        /// The box from `(0, 0)` to `(max, max)` where part 2 looks for the distress beacon.
+        pub(crate) fn square(max: i64) -> Self {
+            Self {
+                min: (0, 0),
+                max: (max, max),
+            }
+        }
+
+        /// The smallest rotated rectangle holding the whole box.
+        pub(crate) fn rotated(&self) -> RotatedRect {
+            let ((x0, y0), (x1, y1)) = (self.min, self.max);
+            RotatedRect {
+                u: (x0 + y0, x1 + y1),
+                v: (x0 - y1, x1 - y0),
+            }
+        }
+    }
+
+    /// An inclusive rectangle in rotated coordinates. Only the points where `u` and `v` have the
+    /// same parity are cells, the others fall between cells.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub(crate) struct RotatedRect {
+        pub(crate) u: (i64, i64),
+        pub(crate) v: (i64, i64),
+    }
+
+    /// How many even and odd numbers are in `[lo, hi]`.
+    fn parities((lo, hi): (i64, i64)) -> (u64, u64) {
+        let even = hi.div_euclid(2) - (lo - 1).div_euclid(2);
+        let odd = hi - lo + 1 - even;
+        (even as u64, odd as u64)
+    }
+
+    impl RotatedRect {
+        /// The cells no further than `radius` from `center`.
+        pub(crate) fn diamond((x, y): Coordinate, radius: i64) -> Self {
+            Self {
+                u: (x + y - radius, x + y + radius),
+                v: (x - y - radius, x - y + radius),
+            }
+        }
+
+        pub(crate) fn intersection(&self, other: &Self) -> Option<Self> {
+            let overlap = |(lo1, hi1): (i64, i64), (lo2, hi2): (i64, i64)| {
+                let (lo, hi) = (lo1.max(lo2), hi1.min(hi2));
+                (lo <= hi).then_some((lo, hi))
+            };
+            Some(Self {
+                u: overlap(self.u, other.u)?,
+                v: overlap(self.v, other.v)?,
+            })
+        }
+
+        /// The points of `self` outside `other`, as at most four disjoint rectangles.
+        pub(crate) fn difference(&self, other: &Self) -> Vec<Self> {
+            let Some(inner) = self.intersection(other) else {
+                return vec![*self];
+            };
+            let mut pieces = vec![];
+            if self.u.0 < inner.u.0 {
+                pieces.push(Self {
+                    u: (self.u.0, inner.u.0 - 1),
+                    v: self.v,
+                });
+            }
+            if inner.u.1 < self.u.1 {
+                pieces.push(Self {
+                    u: (inner.u.1 + 1, self.u.1),
+                    v: self.v,
+                });
+            }
+            if self.v.0 < inner.v.0 {
+                pieces.push(Self {
+                    u: inner.u,
+                    v: (self.v.0, inner.v.0 - 1),
+                });
+            }
+            if inner.v.1 < self.v.1 {
+                pieces.push(Self {
+                    u: inner.u,
+                    v: (inner.v.1 + 1, self.v.1),
+                });
+            }
+            pieces
+        }
+
+        pub(crate) fn contains(&self, (x, y): Coordinate) -> bool {
+            (self.u.0..=self.u.1).contains(&(x + y)) && (self.v.0..=self.v.1).contains(&(x - y))
+        }
+
+        pub(crate) fn cell_count(&self) -> u64 {
+            let (u_even, u_odd) = parities(self.u);
+            let (v_even, v_odd) = parities(self.v);
+            u_even * v_even + u_odd * v_odd
+        }
+
+        /// The cells of the rectangle that are inside `bbox`, column by column in `u`.
+        pub(crate) fn cells_in(self, bbox: BoundingBox) -> impl Iterator<Item = Coordinate> {
+            let ((x0, y0), (x1, y1)) = (bbox.min, bbox.max);
+            let outer = bbox.rotated();
+            (self.u.0.max(outer.u.0)..=self.u.1.min(outer.u.1)).flat_map(move |u| {
+                // x = (u + v) / 2 and y = (u - v) / 2 both have to stay inside the box.
+                let lo = self.v.0.max(2 * x0 - u).max(u - 2 * y1);
+                let hi = self.v.1.min(2 * x1 - u).min(u - 2 * y0);
+                let first = lo + (u - lo).rem_euclid(2);
+                (first..=hi)
+                    .step_by(2)
+                    .map(move |v| ((u + v) / 2, (u - v) / 2))
+            })
+        }
+    }
+
+    /// One rectangle per sensor, covering every cell its beacon rules out (and the beacon itself).
+    pub(crate) fn diamonds(input: &[Report]) -> Vec<RotatedRect> {
+        input
+            .iter()
+            .map(|&(sensor, beacon)| {
+                let radius = distance(sensor, beacon);
+                RotatedRect::diamond(
+                    sensor,
+                    i64::try_from(radius).expect("distance cannot fit in an `i64`"),
+                )
+            })
+            .collect()
+    }
+
+    /// Cut the `u` axis at every rectangle edge and the extra `cuts`, and return each slab between
+    /// two cuts along with the `v` intervals covered all the way across it.
+    ///
+    /// Sweeps the slabs in order, keeping only the rectangles that span the current one.
+    fn slabs(rects: &[RotatedRect], cuts: &[i64]) -> Vec<((i64, i64), Intervals)> {
+        let events = rects
+            .iter()
+            .flat_map(|r| [r.u.0, r.u.1 + 1])
+            .chain(cuts.iter().copied())
+            .sorted()
+            .dedup()
+            .collect_vec();
+        let mut pending = rects.iter().sorted_by_key(|r| r.u.0).peekable();
+        let mut active: Vec<&RotatedRect> = vec![];
+        events
+            .iter()
+            .tuple_windows()
+            .map(|(&start, &end)| {
+                while let Some(r) = pending.next_if(|r| r.u.0 <= start) {
+                    active.push(r);
+                }
+                active.retain(|r| start <= r.u.1);
+                let covered = active
+                    .iter()
+                    .map(|r| r.v)
+                    .fold(Intervals::new(), |mut acc, v| {
+                        acc.insert(v);
+                        acc
+                    });
+                ((start, end - 1), covered)
+            })
+            .collect()
+    }
+
+    /// Number of cells within reach of at least one sensor.
+    pub(crate) fn covered_area(input: &[Report]) -> u64 {
+        slabs(&diamonds(input), &[])
+            .into_iter()
+            .flat_map(|(u, covered)| covered.0.into_iter().map(move |v| RotatedRect { u, v }))
+            .map(|r| r.cell_count())
+            .sum()
+    }
+
+    /// The parts of `bbox` no sensor reaches, as rotated rectangles. A rectangle can stick out of
+    /// the box, but always has at least one cell inside it.
+    pub(crate) fn uncovered_regions(input: &[Report], bbox: BoundingBox) -> Vec<RotatedRect> {
+        uncovered_within(&diamonds(input), bbox.rotated(), bbox)
+    }
+
+    /// The points of `window` outside every one of `rects`, keeping only the rectangles that have
+    /// a cell inside `bbox`.
+    pub(crate) fn uncovered_within(
+        rects: &[RotatedRect],
+        window: RotatedRect,
+        bbox: BoundingBox,
+    ) -> Vec<RotatedRect> {
+        slabs(rects, &[window.u.0, window.u.1 + 1])
+            .into_iter()
+            .filter(|&((start, end), _)| window.u.0 <= start && end <= window.u.1)
+            .flat_map(|(u, covered)| {
+                let mut gaps = Intervals::from_iter([window.v]);
+                for v in covered.0 {
+                    gaps.remove(v);
+                }
+                gaps.0.into_iter().map(move |v| RotatedRect { u, v })
+            })
+            .filter(|r| r.cells_in(bbox).next().is_some())
+            .collect()
+    }
+
+    /// Every cell of `bbox` no sensor reaches, sorted.
+    pub(crate) fn uncovered_cells(input: &[Report], bbox: BoundingBox) -> Vec<Coordinate> {
+        uncovered_regions(input, bbox)
+            .into_iter()
+            .flat_map(|r| r.cells_in(bbox))
+            .sorted()
+            .collect()
+    }
+
+    /// How many sensors reach each pixel of a `width` x `height` image of `bbox`, sampling the
+    /// cell at the center of every pixel.
+    pub(crate) fn coverage_map(
+        input: &[Report],
+        bbox: BoundingBox,
+        width: usize,
+        height: usize,
+    ) -> Vec<Vec<usize>> {
+        let sample = |lo: i64, hi: i64, i: usize, n: usize| {
+            lo + ((2 * i as i64 + 1) * (hi - lo + 1)).div_euclid(2 * n as i64)
+        };
+        (0..height)
+            .map(|row| {
+                let y = sample(bbox.min.1, bbox.max.1, row, height);
+                (0..width)
+                    .map(|col| {
+                        let x = sample(bbox.min.0, bbox.max.0, col, width);
+                        input
+                            .iter()
+                            .filter(|&&(sensor, beacon)| {
+                                distance(sensor, (x, y)) <= distance(sensor, beacon)
+                            })
+                            .count()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Render a coverage map as text: `.` where no sensor reaches, otherwise the number of
+    /// overlapping sensors, with `+` for ten or more.
+    pub(crate) fn coverage_ascii(counts: &[Vec<usize>]) -> String {
+        counts
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&n| match n {
+                        0 => '.',
+                        1..=9 => char::from_digit(n as u32, 10).expect("a single digit"),
+                        _ => '+',
+                    })
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
+
+    /// Write a coverage map as a plain PGM image, brighter where more sensors overlap.
+    pub(crate) fn coverage_pgm(
+        counts: &[Vec<usize>],
+        out: &mut impl std::io::Write,
+    ) -> std::io::Result<()> {
+        let height = counts.len();
+        let width = counts.first().map_or(0, |row| row.len());
+        let max = counts.iter().flatten().copied().max().unwrap_or(0).max(1);
+        writeln!(out, "P2\n{width} {height}\n255")?;
+        for row in counts {
+            writeln!(out, "{}", row.iter().map(|&n| n * 255 / max).join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A sensor field that changes over time, answering queries from caches that are patched on every
+/// change rather than rebuilt.
+mod network {
+    use super::geometry::{self, BoundingBox, RotatedRect};
+    use super::{disallowed_x_at_y, distance, Coordinate, Intervals, Report};
+    use std::collections::{BTreeMap, HashMap};
+
+    /// How many times more pieces than a fresh sweep a box's gaps may be split into by patching
+    /// before they are swept again.
+    const FRAGMENTATION_LIMIT: usize = 4;
+
+    /// The uncovered rectangles of a box, and how many a fresh sweep found.
+    #[derive(Debug)]
+    struct Gaps {
+        rects: Vec<RotatedRect>,
+        swept: usize,
+    }
+
+    impl Gaps {
+        fn sweep(sensors: &[RotatedRect], bbox: BoundingBox) -> Self {
+            let rects = geometry::uncovered_within(sensors, bbox.rotated(), bbox);
+            Self {
+                swept: rects.len(),
+                rects,
+            }
+        }
+
+        fn is_fragmented(&self) -> bool {
+            self.rects.len() > FRAGMENTATION_LIMIT * self.swept.max(1)
+        }
+    }
+
+    /// Handle to a sensor in a [`SensorNetwork`], for removing it again.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub(crate) struct SensorId(usize);
+
+    #[derive(Debug, Default)]
+    pub(crate) struct SensorNetwork {
+        next_id: usize,
+        sensors: BTreeMap<SensorId, (Report, RotatedRect)>,
+        /// How many sensors report each beacon, keyed by `(y, x)` so a row is a range.
+        beacons: BTreeMap<(i64, i64), usize>,
+        /// Covered x intervals of every row asked about so far.
+        rows: HashMap<i64, Intervals>,
+        /// Uncovered rotated rectangles of every box asked about so far.
+        gaps: HashMap<BoundingBox, Gaps>,
+    }
+
+    impl SensorNetwork {
+        pub(crate) fn new() -> Self {
+            Self::default()
+        }
+
+        pub(crate) fn len(&self) -> usize {
+            self.sensors.len()
+        }
+
+        #[cfg(test)]
+        pub(crate) fn reports(&self) -> impl Iterator<Item = Report> + '_ {
+            self.sensors.values().map(|&(report, _)| report)
+        }
+
+        pub(crate) fn insert(&mut self, report @ (sensor, beacon): Report) -> SensorId {
+            let id = SensorId(self.next_id);
+            self.next_id += 1;
+            let rect = geometry::diamonds(&[report])[0];
+            self.sensors.insert(id, (report, rect));
+            *self.beacons.entry((beacon.1, beacon.0)).or_default() += 1;
+            // A new sensor only ever covers more, so the caches just lose what it reaches.
+            for (&y, covered) in &mut self.rows {
+                if let Some(interval) = disallowed_x_at_y(sensor, beacon, y) {
+                    covered.insert(interval);
+                }
+            }
+            for (bbox, gaps) in &mut self.gaps {
+                gaps.rects = gaps
+                    .rects
+                    .iter()
+                    .flat_map(|gap| gap.difference(&rect))
+                    .filter(|gap| gap.cells_in(*bbox).next().is_some())
+                    .collect();
+            }
+            self.sweep_fragmented_gaps();
+            id
+        }
+
+        pub(crate) fn remove(&mut self, id: SensorId) -> Option<Report> {
+            let ((sensor, beacon), rect) = self.sensors.remove(&id)?;
+            let key = (beacon.1, beacon.0);
+            if let Some(count) = self.beacons.get_mut(&key) {
+                *count -= 1;
+                if *count == 0 {
+                    self.beacons.remove(&key);
+                }
+            }
+            // Other sensors may overlap the removed one, so the rows it reached are dropped and
+            // rebuilt on the next query.
+            let radius = distance(sensor, beacon);
+            self.rows.retain(|&y, _| sensor.1.abs_diff(y) > radius);
+            // Cells can only open up inside the removed diamond, and every cached gap lies
+            // outside it, so the new gaps are simply added.
+            let rects = self.rects();
+            for (bbox, gaps) in &mut self.gaps {
+                if let Some(window) = rect.intersection(&bbox.rotated()) {
+                    gaps.rects
+                        .extend(geometry::uncovered_within(&rects, window, *bbox));
+                }
+            }
+            self.sweep_fragmented_gaps();
+            Some((sensor, beacon))
+        }
+
+        fn rects(&self) -> Vec<RotatedRect> {
+            self.sensors.values().map(|&(_, r)| r).collect()
+        }
+
+        /// Patching splits gaps into ever smaller pieces, so start over once there are too many.
+        fn sweep_fragmented_gaps(&mut self) {
+            if !self.gaps.values().any(Gaps::is_fragmented) {
+                return;
+            }
+            let rects = self.rects();
+            for (&bbox, gaps) in &mut self.gaps {
+                if gaps.is_fragmented() {
+                    *gaps = Gaps::sweep(&rects, bbox);
+                }
+            }
+        }
+
+        /// How many rectangles the gaps of `bbox` are currently split into.
+        #[cfg(test)]
+        pub(crate) fn gap_pieces(&self, bbox: BoundingBox) -> Option<usize> {
+            self.gaps.get(&bbox).map(|gaps| gaps.rects.len())
+        }
+
+        /// Whether `p` is within reach of some sensor. Every beacon is in reach of its own sensor.
+        pub(crate) fn is_covered(&self, p: Coordinate) -> bool {
+            self.sensors.values().any(|&(_, rect)| rect.contains(p))
+        }
+
+        /// Same as [`super::count_impossible_positions_at_row`].
+        pub(crate) fn impossible_positions_at_row(&mut self, y: i64) -> usize {
+            let sensors = &self.sensors;
+            let covered = self.rows.entry(y).or_insert_with(|| {
+                let mut covered = Intervals::new();
+                for &((sensor, beacon), _) in sensors.values() {
+                    if let Some(interval) = disallowed_x_at_y(sensor, beacon, y) {
+                        covered.insert(interval);
+                    }
+                }
+                covered
+            });
+            let beacons = self.beacons.range((y, i64::MIN)..=(y, i64::MAX)).count();
+            covered.size() - beacons
+        }
+
+        /// Every cell of `bbox` no sensor reaches, sorted.
+        pub(crate) fn gaps(&mut self, bbox: BoundingBox) -> Vec<Coordinate> {
+            if !self.gaps.contains_key(&bbox) {
+                let gaps = Gaps::sweep(&self.rects(), bbox);
+                self.gaps.insert(bbox, gaps);
+            }
+            let mut cells = self.gaps[&bbox]
+                .rects
+                .iter()
+                .flat_map(|gap| gap.cells_in(bbox))
+                .collect::<Vec<_>>();
+            cells.sort_unstable();
+            cells
+        }
+    }
+}
+
+/// Split off an optional `# row=N max=N` header line, and parse the sensors.
+fn parse_input(s: &str) -> anyhow::Result<(Option<&str>, Vec<Report>)> {
+    let mut lines = s.lines().peekable();
+    let header = lines.next_if(|line| line.starts_with('#'));
+    let input = lines
+        .filter(|line| !line.trim().is_empty())
         .map(parse)
         .collect::<anyhow::Result<Vec<_>>>()?;
-    println!(
-        "q1: {}",
-        count_impossible_positions_at_row(&input, TARGET_ROW),
-    );
-    let (q2_x, q2_y) = find_beacon(&input, MAX_BEACON_VAL);
-    println!("q2: {}", q2_x * 4000000 + q2_y);
+    Ok((header.map(|h| &h[1..]), input))
+}
+
+fn q1(input: &[Report], bounds: Bounds) -> usize {
+    count_impossible_positions_at_row(input, bounds.row)
+}
+
+fn q2(input: &[Report], bounds: Bounds) -> i64 {
+    let (x, y) = find_beacon(input, bounds.max);
+    x * TUNING_MULTIPLIER + y
+}
+
+fn main() -> anyhow::Result<()> {
+    const USAGE: &str = "usage: aoc_22_15 [--sample] [--row N] [--max N] [--area] [--gaps] \
+        [--coverage WIDTHxHEIGHT [--pgm]] [--network] [--offline SENSOR]... [--covered X,Y]... \
+        < input";
+    let mut preset = None;
+    let mut show_area = false;
+    let mut show_gaps = false;
+    let mut use_network = false;
+    let mut offline = vec![];
+    let mut probes = vec![];
+    let mut coverage = None;
+    let mut pgm = false;
+    let mut overrides = String::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sample" => preset = Some(Bounds::SAMPLE),
+            "--row" | "--max" => {
+                let n = args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                overrides += &format!(" {}={n}", &arg[2..]);
+            }
+            "--area" => show_area = true,
+            "--gaps" => show_gaps = true,
+            "--coverage" => {
+                let size = args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                let (width, height) = size.split_once('x').ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                coverage = Some((width.parse::<usize>()?, height.parse::<usize>()?));
+            }
+            "--pgm" => pgm = true,
+            "--network" => use_network = true,
+            "--offline" => {
+                let n = args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                offline.push(n.parse::<usize>()?);
+                use_network = true;
+            }
+            "--covered" => {
+                let p = args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                let (x, y) = p.split_once(',').ok_or_else(|| anyhow::anyhow!(USAGE))?;
+                probes.push((x.parse::<i64>()?, y.parse::<i64>()?));
+                use_network = true;
+            }
+            _ => anyhow::bail!(USAGE),
+        }
+    }
+    let input = std::io::read_to_string(std::io::stdin())?;
+    let (header, input) = parse_input(&input)?;
+    // Command line arguments take precedence over the header.
+    let mut bounds = Bounds::PUZZLE;
+    if let Some(header) = header {
+        bounds.apply(header)?;
+    }
+    if let Some(preset) = preset {
+        bounds = preset;
+    }
+    bounds.apply(&overrides)?;
+
+    let bbox = geometry::BoundingBox::square(bounds.max);
+    if let Some((width, height)) = coverage {
+        let counts = geometry::coverage_map(&input, bbox, width, height);
+        if pgm {
+            return Ok(geometry::coverage_pgm(
+                &counts,
+                &mut std::io::stdout().lock(),
+            )?);
+        }
+        println!("{}", geometry::coverage_ascii(&counts));
+    }
+    if show_area {
+        println!("covered area: {}", geometry::covered_area(&input));
+    }
+    if use_network {
+        // Answer from the incremental network instead, which can take sensors offline.
+        let mut network = network::SensorNetwork::new();
+        let ids = input
+            .iter()
+            .map(|&report| network.insert(report))
+            .collect::<Vec<_>>();
+        for n in offline {
+            let id = n
+                .checked_sub(1)
+                .and_then(|i| ids.get(i))
+                .ok_or_else(|| anyhow::anyhow!("there is no sensor {n}"))?;
+            if let Some((sensor, _)) = network.remove(*id) {
+                println!("offline: sensor {n} at {sensor:?}");
+            }
+        }
+        println!("network: {} sensors", network.len());
+        for p in probes {
+            println!("covered {p:?}: {}", network.is_covered(p));
+        }
+        println!(
+            "impossible positions at row {}: {}",
+            bounds.row,
+            network.impossible_positions_at_row(bounds.row)
+        );
+        if show_gaps {
+            for cell in network.gaps(bbox) {
+                println!("uncovered: {cell:?}");
+            }
+        }
+    } else if show_gaps {
+        for cell in geometry::uncovered_cells(&input, bbox) {
+            println!("uncovered: {cell:?}");
+        }
+    }
+
+    println!("q1: {}", q1(&input, bounds));
+    println!("q2: {}", q2(&input, bounds));
     Ok(())
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+    use itertools::Itertools;
+    use proptest::prelude::*;
+
+    const SAMPLE: &str = include_str!("../sample_input.txt");
+
+    #[test]
+    fn test_sample() {
+        let (header, input) = parse_input(SAMPLE).unwrap();
+        assert_eq!(header, None);
+        assert_eq!(input.len(), 14);
+        assert_eq!(q1(&input, Bounds::SAMPLE), 26);
+        assert_eq!(find_beacon(&input, Bounds::SAMPLE.max), (14, 11));
+        assert_eq!(q2(&input, Bounds::SAMPLE), 56000011);
+    }
+
+    #[test]
+    fn test_counts_the_given_row() {
+        let (_, input) = parse_input(SAMPLE).unwrap();
+        for row in -15..35 {
+            let brute_force = (-50..50)
+                .filter(|&x| {
+                    input.iter().any(|&(sensor, beacon)| {
+                        (x, row) != beacon && distance(sensor, (x, row)) <= distance(sensor, beacon)
+                    })
+                })
+                .count();
+            assert_eq!(count_impossible_positions_at_row(&input, row), brute_force, "{row}");
+        }
+    }
+
+    #[test]
+    fn test_header() {
+        let input = format!("# row=10 max=20\n{SAMPLE}");
+        let (header, input) = parse_input(&input).unwrap();
+        let mut bounds = Bounds::PUZZLE;
+        bounds.apply(header.unwrap()).unwrap();
+        assert_eq!(bounds, Bounds::SAMPLE);
+        assert_eq!(q1(&input, bounds), 26);
+        assert_eq!(q2(&input, bounds), 56000011);
+        assert!(bounds.apply("row=1 col=2").is_err());
+        assert!(bounds.apply("row=x").is_err());
+    }
+
+    #[test]
+    fn test_single_diamond_area() {
+        for radius in 0..6 {
+            let input = [((3, -2), (3 + radius, -2))];
+            let expected = 2 * radius * radius + 2 * radius + 1;
+            assert_eq!(geometry::covered_area(&input), expected as u64, "{radius}");
+        }
+    }
+
+    #[test]
+    fn test_covered_area() {
+        let (_, input) = parse_input(SAMPLE).unwrap();
+        let brute_force = (-20..50)
+            .flat_map(|y| (-20..50).map(move |x| (x, y)))
+            .filter(|&p| {
+                input
+                    .iter()
+                    .any(|&(sensor, beacon)| distance(sensor, p) <= distance(sensor, beacon))
+            })
+            .count();
+        assert_eq!(geometry::covered_area(&input), brute_force as u64);
+        // Every sensor's diamond overlaps at least one other.
+        let separate: u64 = input
+            .iter()
+            .map(|&report| geometry::covered_area(&[report]))
+            .sum();
+        assert!(separate > brute_force as u64);
+    }
+
+    #[test]
+    fn test_uncovered_cells() {
+        use geometry::BoundingBox;
+        let (_, input) = parse_input(SAMPLE).unwrap();
+        let bbox = BoundingBox::square(Bounds::SAMPLE.max);
+        assert_eq!(geometry::uncovered_cells(&input, bbox), [(14, 11)]);
+
+        for bbox in [
+            BoundingBox {
+                min: (-8, -3),
+                max: (30, 25),
+            },
+            BoundingBox {
+                min: (17, 0),
+                max: (19, 40),
+            },
+            BoundingBox {
+                min: (5, 5),
+                max: (5, 5),
+            },
+        ] {
+            let brute_force = (bbox.min.1..=bbox.max.1)
+                .flat_map(|y| (bbox.min.0..=bbox.max.0).map(move |x| (x, y)))
+                .filter(|&p| {
+                    input
+                        .iter()
+                        .all(|&(sensor, beacon)| distance(sensor, p) > distance(sensor, beacon))
+                })
+                .sorted_by_key(|&(x, y)| (x, y))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                geometry::uncovered_cells(&input, bbox),
+                brute_force,
+                "{bbox:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_coverage_map() {
+        let (_, input) = parse_input(SAMPLE).unwrap();
+        let bbox = geometry::BoundingBox::square(Bounds::SAMPLE.max);
+        // One pixel per cell.
+        let counts = geometry::coverage_map(&input, bbox, 21, 21);
+        assert_eq!(counts[11][14], 0);
+        for (y, row) in counts.iter().enumerate() {
+            for (x, &count) in row.iter().enumerate() {
+                let p = (x as i64, y as i64);
+                let expected = input
+                    .iter()
+                    .filter(|&&(sensor, beacon)| distance(sensor, p) <= distance(sensor, beacon))
+                    .count();
+                assert_eq!(count, expected, "{p:?}");
+            }
+        }
+        let image = geometry::coverage_ascii(&geometry::coverage_map(&input, bbox, 7, 3));
+        assert_eq!(image.lines().map(str::len).collect::<Vec<_>>(), [7, 7, 7]);
+        assert!(image.chars().any(|c| ('2'..='9').contains(&c)));
+
+        let mut pgm = vec![];
+        geometry::coverage_pgm(&counts, &mut pgm).unwrap();
+        assert!(String::from_utf8(pgm)
+            .unwrap()
+            .starts_with("P2\n21 21\n255\n"));
+    }
+
+    /// The uncovered cells of `bbox`, row by row with [`Intervals`] the way [`find_beacon`] does.
+    fn gaps_by_row(input: &[Report], bbox: geometry::BoundingBox) -> Vec<Coordinate> {
+        let mut cells = vec![];
+        for y in bbox.min.1..=bbox.max.1 {
+            let mut row = Intervals::from_iter([(bbox.min.0, bbox.max.0)]);
+            for &(sensor, beacon) in input {
+                if let Some(forbidden) = disallowed_x_at_y(sensor, beacon, y) {
+                    row.remove(forbidden);
+                }
+            }
+            cells.extend(
+                row.0
+                    .into_iter()
+                    .flat_map(|(min, max)| (min..=max).map(move |x| (x, y))),
+            );
+        }
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn test_network_sample() {
+        let (_, input) = parse_input(SAMPLE).unwrap();
+        let bbox = geometry::BoundingBox::square(Bounds::SAMPLE.max);
+        let mut network = network::SensorNetwork::new();
+        // Ask first so the caches are patched, rather than built, as sensors arrive.
+        assert_eq!(network.impossible_positions_at_row(10), 0);
+        assert_eq!(network.gaps(bbox).len(), 21 * 21);
+        let ids = input
+            .iter()
+            .map(|&report| network.insert(report))
+            .collect::<Vec<_>>();
+        assert_eq!(network.impossible_positions_at_row(10), 26);
+        assert_eq!(network.gaps(bbox), [(14, 11)]);
+        assert!(!network.is_covered((14, 11)));
+        assert!(network.is_covered((2, 18)));
+
+        let removed = network.remove(ids[6]).unwrap();
+        assert_eq!(removed, input[6]);
+        assert_eq!(network.remove(ids[6]), None);
+        let rest = network.reports().collect::<Vec<_>>();
+        assert_eq!(rest.len(), input.len() - 1);
+        assert_eq!(
+            network.impossible_positions_at_row(10),
+            count_impossible_positions_at_row(&rest, 10)
+        );
+        assert_eq!(network.gaps(bbox), gaps_by_row(&rest, bbox));
+        assert!(network.gaps(bbox).len() > 1);
+    }
+
+    #[test]
+    fn test_network_gaps_stay_compact() {
+        let bbox = geometry::BoundingBox::square(40);
+        let wall = ((20, 20), (20, 25));
+        let fresh = geometry::uncovered_within(&geometry::diamonds(&[wall]), bbox.rotated(), bbox);
+        let mut network = network::SensorNetwork::new();
+        let wall = network.insert(wall);
+        let cells = network.gaps(bbox);
+        // Every short-lived sensor splits the cached gaps, and taking it away doesn't merge them.
+        for i in 0..200i64 {
+            let sensor = ((i * 7) % 41, (i * 13) % 41);
+            let id = network.insert((sensor, (sensor.0 + 1, sensor.1)));
+            network.remove(id);
+            assert!(network.gap_pieces(bbox).unwrap() <= 10 * fresh.len());
+        }
+        assert_eq!(network.gaps(bbox), cells);
+        network.remove(wall);
+        assert_eq!(network.gaps(bbox).len(), 41 * 41);
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Insert(Report),
+        Remove(usize),
+    }
+
+    fn arb_op() -> impl Strategy<Value = Op> {
+        let coordinate = || (-12i64..12, -12i64..12);
+        prop_oneof![
+            (coordinate(), -5i64..5, -5i64..5)
+                .prop_map(|(sensor, dx, dy)| Op::Insert((sensor, (sensor.0 + dx, sensor.1 + dy)))),
+            any::<usize>().prop_map(Op::Remove),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_network_matches_intervals(ops in prop::collection::vec(arb_op(), 1..25)) {
+            let bbox = geometry::BoundingBox { min: (-6, -4), max: (8, 9) };
+            let mut network = network::SensorNetwork::new();
+            let mut ids = vec![];
+            for op in ops {
+                match op {
+                    Op::Insert(report) => ids.push(network.insert(report)),
+                    Op::Remove(i) if !ids.is_empty() => {
+                        let id = ids.swap_remove(i % ids.len());
+                        prop_assert!(network.remove(id).is_some());
+                    }
+                    Op::Remove(_) => (),
+                }
+                let reports = network.reports().collect::<Vec<_>>();
+                for y in [-3, 0, 4] {
+                    prop_assert_eq!(
+                        network.impossible_positions_at_row(y),
+                        count_impossible_positions_at_row(&reports, y)
+                    );
+                }
+                let gaps = network.gaps(bbox);
+                prop_assert_eq!(&gaps, &gaps_by_row(&reports, bbox));
+                for x in bbox.min.0..=bbox.max.0 {
+                    prop_assert_eq!(network.is_covered((x, 2)), gaps.binary_search(&(x, 2)).is_err());
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test_interval {
     use super::Intervals;
@@ -332,6 +1184,17 @@ mod test_interval {
             let target = Intervals::from_iter([(10, 40)]);
             assert_eq!(interval, target);
         }
+        {
+            let mut interval = original.clone();
+            interval.insert((5, 45));
+            assert_eq!(interval, Intervals::from_iter([(5, 45)]));
+        }
+        {
+            let mut interval = Intervals::from_iter([(6, 10), (12, 14), (14, 26)]);
+            interval.insert((-8, 12));
+            assert_eq!(interval, Intervals::from_iter([(-8, 26)]));
+            assert_eq!(interval.size(), 35);
+        }
     }
 
     #[test]